@@ -0,0 +1,73 @@
+use ferrocyanide::{config::AppConfig, templates::load_templates};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+const SECTIONS: usize = 10;
+const PAGES_PER_SECTION: usize = 50;
+
+/// The docs site with `SECTIONS * PAGES_PER_SECTION` generated pages.
+static SITE: OnceLock<PathBuf> = OnceLock::new();
+
+fn main() {
+    // removed again when `temp` is dropped after the benches
+    let temp = tempfile::tempdir().unwrap();
+    create_site(temp.path());
+    SITE.set(temp.path().to_path_buf()).unwrap();
+    divan::main();
+}
+
+fn create_site(site: &Path) {
+    let docs = Path::new(env!("CARGO_MANIFEST_DIR")).join("docs");
+    copy_dir(&docs, site);
+
+    for section in 0..SECTIONS {
+        let dir = site.join("content").join(format!("section-{section}"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!("section-{section}.yaml")), "layout: doc.html").unwrap();
+        for page in 0..PAGES_PER_SECTION {
+            fs::write(dir.join(format!("page-{page}.md")), page_source(section, page)).unwrap();
+        }
+    }
+}
+
+fn page_source(section: usize, page: usize) -> String {
+    let mut source = format!("---\ntitle: Page {section}.{page}\n---\n\n# Page {section}.{page}\n\n");
+    for heading in 0..5 {
+        source.push_str(&format!("## Heading {heading}\n\nSome *text* with a [link](https://example.com).\n\n"));
+        source.push_str("```rust\nfn main() {\n    println!(\"Hello, world!\");\n}\n```\n\n");
+    }
+    source
+}
+
+fn copy_dir(src: &Path, dst: &Path) {
+    for entry in walkdir::WalkDir::new(src) {
+        let entry = entry.unwrap();
+        let target = dst.join(entry.path().strip_prefix(src).unwrap());
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(target).unwrap();
+        } else {
+            fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
+
+#[divan::bench(sample_count = 10)]
+fn build_site() {
+    let site = SITE.get().unwrap();
+    ferrocyanide::build::build(Some(site.clone()), &site.join("dist"), None).unwrap();
+}
+
+/// The work saved by compiling templates once, `build` used to do this for every page.
+#[divan::bench(sample_count = 10)]
+fn compile_templates_per_page() {
+    let config = AppConfig {
+        folder: SITE.get().unwrap().clone(),
+        ..Default::default()
+    };
+    for _ in 0..SECTIONS * PAGES_PER_SECTION {
+        divan::black_box(load_templates(&config).unwrap());
+    }
+}
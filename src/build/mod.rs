@@ -1,35 +1,39 @@
-use crate::{CONTEXT, Context, content::COLOR_PICKER_JS, read_config, render};
+use crate::{CONTEXT, Context, Error, Result, content::COLOR_PICKER_JS, read_config, render};
 use std::{
     ffi::OsStr,
     fs, io,
     path::{Path, PathBuf},
 };
 
-pub fn build(folder: Option<PathBuf>) -> anyhow::Result<()> {
+/// Renders the site in `folder` into `out_dir`, which is replaced entirely.
+pub fn build(folder: Option<PathBuf>, out_dir: &Path, env: Option<&str>) -> Result<()> {
     *CONTEXT.write().unwrap() = Context::Build;
-    let out_dir = Path::new("dist");
+    tracing_subscriber::fmt().with_target(false).try_init().ok();
     fs::remove_dir_all(out_dir).ok(); //ignore if fails
-    fs::create_dir(out_dir)?;
+    fs::create_dir(out_dir).map_err(Error::io(out_dir))?;
     let root = folder.unwrap_or_default();
     let content = root.join("content");
-    copy_dir_all(root.join("assets"), out_dir.join("assets"))?;
-
-    fs::create_dir_all(out_dir.join("js"))?;
-    fs::write(out_dir.join("js").join("SwitchColorMode.js"), COLOR_PICKER_JS)?;
+    let config = read_config(Some(root), env)?;
+    // theme assets first, so project assets replace them
+    for assets in config.asset_dirs().iter().rev().filter(|dir| dir.is_dir()) {
+        copy_dir_all(assets, out_dir.join("assets")).map_err(Error::io(assets))?;
+    }
 
-    let config = read_config(Some(root))?;
+    let js_dir = out_dir.join("js");
+    fs::create_dir_all(&js_dir).map_err(Error::io(&js_dir))?;
+    fs::write(js_dir.join("SwitchColorMode.js"), COLOR_PICKER_JS).map_err(Error::io(&js_dir))?;
 
     for file in walkdir::WalkDir::new(&content) {
-        let file = file?;
+        let file = file.map_err(|err| Error::io(&content)(err.into()))?;
         if file.path().extension() == Some(OsStr::new("md")) {
             let contents = render(file.path(), &config)?;
-            let filename = file.path().strip_prefix(&content)?;
+            let filename = file.path().strip_prefix(&content).unwrap_or(file.path());
             let filename = filename.with_extension("html");
             let filename = out_dir.join(filename);
             if let Some(parent) = filename.parent() {
-                fs::create_dir_all(parent)?;
+                fs::create_dir_all(parent).map_err(Error::io(parent))?;
             }
-            fs::write(filename, contents)?;
+            fs::write(&filename, contents).map_err(Error::io(&filename))?;
         }
     }
 
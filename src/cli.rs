@@ -6,6 +6,9 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// Merges `config.<env>.yaml` over `config.yaml`
+    #[arg(long, global = true, env = "FERROCYANIDE_ENV")]
+    pub env: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -14,4 +17,16 @@ pub enum Commands {
     Serve { folder: Option<PathBuf> },
     #[command(about = "Builds the site as static html files")]
     Build { folder: Option<PathBuf> },
+    #[command(about = "Prints the effective config of the site")]
+    Config {
+        folder: Option<PathBuf>,
+        #[command(subcommand)]
+        command: Option<ConfigCommands>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    #[command(about = "Prints the JSON Schema of config.yaml for editor completion")]
+    Schema,
 }
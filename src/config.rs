@@ -1,40 +1,428 @@
-use crate::content::page::Page;
+use crate::{
+    Error, Result,
+    content::{
+        cascade,
+        frontmatter::FrontmatterMode,
+        math::MathRenderer,
+        page::{Page, content_url},
+    },
+    data::Data,
+    templates::Templates,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use serde_yaml::{Mapping, Value};
 use std::{
     collections::HashMap,
+    env, fs,
     path::{Path, PathBuf},
+    sync::Arc,
 };
+use tera::Tera;
+use tracing::warn;
 
 pub fn get_config_path(project_dir: impl AsRef<Path>) -> PathBuf {
     project_dir.as_ref().join("config.yaml")
 }
 
+pub fn get_env_config_path(project_dir: impl AsRef<Path>, env: &str) -> PathBuf {
+    project_dir.as_ref().join(format!("config.{env}.yaml"))
+}
+
+pub fn get_theme_dir(project_dir: impl AsRef<Path>, theme: &str) -> PathBuf {
+    project_dir.as_ref().join("themes").join(theme)
+}
+
+/// Reads `config.yaml` of the project with `config.<env>.yaml` merged over it, the
+/// `config.yaml` of its theme provides the defaults.
+pub fn read_project_config(project_dir: &Path, env: Option<&str>) -> Result<ProjectConfig> {
+    let config_file = get_config_path(project_dir);
+    let mut config = read_mapping(&config_file)?;
+    if let Some(env) = env {
+        let env_file = get_env_config_path(project_dir, env);
+        if !env_file.exists() {
+            return Err(Error::Config {
+                path: env_file,
+                source: format!("environment `{env}` has no config file").into(),
+            });
+        }
+        cascade::merge(&mut config, read_mapping(&env_file)?);
+    }
+    if let Some(theme) = config.get("theme").and_then(Value::as_str) {
+        let theme_dir = get_theme_dir(project_dir, theme);
+        if !theme_dir.is_dir() {
+            return Err(Error::Config {
+                path: config_file,
+                source: format!("theme `{theme}` does not exist, expected {}", theme_dir.display()).into(),
+            });
+        }
+        let mut defaults = read_mapping(&get_config_path(&theme_dir))?;
+        cascade::merge(&mut defaults, config);
+        config = defaults;
+    }
+
+    let mut config = deserialize(&config).map_err(|err| Error::Config {
+        path: config_file,
+        source: err.into(),
+    })?;
+    config.env = env.map(str::to_string);
+    Ok(config)
+}
+
+/// Effective config of the project as YAML, printed by the `config` command.
+pub fn dump_config(project_dir: &Path, env: Option<&str>) -> Result<String> {
+    let config = read_project_config(project_dir, env)?;
+    serde_yaml::to_string(&config).map_err(|err| Error::Config {
+        path: get_config_path(project_dir),
+        source: err.into(),
+    })
+}
+
+/// Mapping of a config file with its variables replaced, which is empty if the file doesn't exist.
+fn read_mapping(path: &Path) -> Result<Mapping> {
+    if !path.exists() {
+        return Ok(Mapping::new());
+    }
+    let source = fs::read_to_string(path).map_err(Error::io(path))?;
+    let invalid = |source| Error::Config {
+        path: path.to_path_buf(),
+        source,
+    };
+    let Value::Mapping(mut mapping) = serde_yaml::from_str(&source).map_err(|err| invalid(err.into()))? else {
+        return Ok(Mapping::new());
+    };
+    for warning in unknown_keys(&mapping) {
+        warn!("{}: {warning}", path.display());
+    }
+    let original = mapping.clone();
+    for value in mapping.values_mut() {
+        interpolate(value, &|name| env::var(name).ok()).map_err(|err| invalid(err.into()))?;
+    }
+    // validate the file on its own first, the merged config has no line numbers, which are
+    // only available if no variables were replaced
+    if mapping == original {
+        serde_yaml::from_str::<ProjectConfig>(&source).map_err(|err| invalid(err.into()))?;
+    } else {
+        deserialize(&mapping).map_err(|err| invalid(err.into()))?;
+    }
+    Ok(mapping)
+}
+
+/// Deserializes a merged config through its YAML text, so plain scalars like numbers are
+/// accepted by string fields just like in a config file.
+fn deserialize(mapping: &Mapping) -> serde_yaml::Result<ProjectConfig> {
+    serde_yaml::from_str(&serde_yaml::to_string(mapping)?)
+}
+
+/// Replaces `${VAR}` and `${VAR:-default}` in all strings of `value` using `lookup`, the
+/// default is used if the variable is unset or empty. Strings with replaced variables are
+/// parsed again, so `${WORDS:-30}` becomes a number and `${DRAFTS}` can be a bool.
+fn interpolate(value: &mut Value, lookup: &dyn Fn(&str) -> Option<String>) -> Result<(), String> {
+    match value {
+        Value::String(text) => {
+            let mut result = String::with_capacity(text.len());
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("${") {
+                result.push_str(&rest[..start]);
+                let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+                    return Err(format!("unclosed `${{` in `{text}`"));
+                };
+                let expression = &rest[start + 2..end];
+                let (name, default) = match expression.split_once(":-") {
+                    Some((name, default)) => (name, Some(default)),
+                    None => (expression, None),
+                };
+                match (lookup(name), default) {
+                    (Some(value), Some(default)) if value.is_empty() => result.push_str(default),
+                    (Some(value), _) => result.push_str(&value),
+                    (None, Some(default)) => result.push_str(default),
+                    (None, None) => return Err(format!("environment variable `{name}` is not set")),
+                }
+                rest = &rest[end + 1..];
+            }
+            result.push_str(rest);
+            if result != *text {
+                *value = match serde_yaml::from_str(&result) {
+                    Ok(scalar @ (Value::Bool(_) | Value::Number(_))) => scalar,
+                    _ => Value::String(result),
+                };
+            }
+        }
+        Value::Sequence(items) => items.iter_mut().try_for_each(|item| interpolate(item, lookup))?,
+        Value::Mapping(mapping) => mapping.values_mut().try_for_each(|item| interpolate(item, lookup))?,
+        Value::Tagged(tagged) => interpolate(&mut tagged.value, lookup)?,
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+    Ok(())
+}
+
+/// JSON Schema of `config.yaml`, printed by `config schema` for editor completion.
+pub fn schema() -> schemars::Schema {
+    schemars::schema_for!(ProjectConfig)
+}
+
+/// Warnings for the keys of `config` which are not part of the schema and thus ignored.
+fn unknown_keys(config: &Mapping) -> Vec<String> {
+    let schema = schema().to_value();
+    let mut unknown = vec![];
+    find_unknown_keys(&Value::Mapping(config.clone()), &schema, &schema["$defs"], "", &mut unknown);
+    unknown
+}
+
+fn find_unknown_keys(value: &Value, schema: &JsonValue, defs: &JsonValue, path: &str, unknown: &mut Vec<String>) {
+    let schema = resolve(schema, defs);
+    if let Some(variants) = schema.get("anyOf").or_else(|| schema.get("oneOf")).and_then(JsonValue::as_array) {
+        // options and untagged enums are checked against the fitting variant with the fewest
+        // unknown keys, free-form variants like the nav shorthand accept any key
+        let fits = |variant: &&JsonValue| match value {
+            Value::Mapping(mapping) => {
+                variant.get("properties").is_some()
+                    && variant["required"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(JsonValue::as_str)
+                        .all(|key| mapping.contains_key(key))
+            }
+            Value::Sequence(_) => variant.get("items").is_some(),
+            _ => false,
+        };
+        let best = variants
+            .iter()
+            .map(|variant| resolve(variant, defs))
+            .filter(fits)
+            .map(|variant| {
+                let mut variant_unknown = vec![];
+                find_unknown_keys(value, variant, defs, path, &mut variant_unknown);
+                variant_unknown
+            })
+            .min_by_key(Vec::len);
+        unknown.extend(best.into_iter().flatten());
+        return;
+    }
+
+    match value {
+        Value::Mapping(mapping) => {
+            let properties = schema.get("properties").and_then(JsonValue::as_object);
+            for (key, value) in mapping {
+                let Some(key) = key.as_str() else {
+                    continue;
+                };
+                let key_path = if path.is_empty() { key.to_string() } else { format!("{path}.{key}") };
+                match (properties, properties.and_then(|properties| properties.get(key))) {
+                    (_, Some(property)) => find_unknown_keys(value, property, defs, &key_path, unknown),
+                    (Some(properties), None) => unknown.push(match suggest(key, properties.keys()) {
+                        Some(suggestion) => format!("unknown key `{key_path}`, did you mean `{suggestion}`?"),
+                        None => format!("unknown key `{key_path}`"),
+                    }),
+                    (None, None) => {
+                        if let Some(values) = schema.get("additionalProperties") {
+                            find_unknown_keys(value, values, defs, &key_path, unknown);
+                        }
+                    }
+                }
+            }
+        }
+        Value::Sequence(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    find_unknown_keys(item, item_schema, defs, &format!("{path}[{i}]"), unknown);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn resolve<'a>(schema: &'a JsonValue, defs: &'a JsonValue) -> &'a JsonValue {
+    schema["$ref"]
+        .as_str()
+        .and_then(|reference| reference.strip_prefix("#/$defs/"))
+        .and_then(|name| defs.get(name))
+        .unwrap_or(schema)
+}
+
+/// Known key most similar to `key`, ignoring case so `rootdir` suggests `rootDir`.
+fn suggest<'a>(key: &str, known: impl Iterator<Item = &'a String>) -> Option<&'a str> {
+    let key = key.to_lowercase();
+    known
+        .map(|candidate| (strsim::jaro_winkler(&key, &candidate.to_lowercase()), candidate))
+        .filter(|(similarity, _)| *similarity > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, candidate)| candidate.as_str())
+}
+
 #[derive(Default, Clone, Debug)]
 pub struct AppConfig {
     pub folder: PathBuf,
-    pub library: HashMap<PathBuf, Page>,
+    pub library: Arc<HashMap<PathBuf, Page>>,
     pub project_config: ProjectConfig,
+    pub templates: Arc<Templates>,
+    pub data: Arc<Data>,
+}
+
+impl AppConfig {
+    pub fn tera(&self) -> Result<Arc<Tera>> {
+        self.templates.get(self)
+    }
+
+    /// Contents of the `data` directory, see [`crate::data::load_dir`].
+    pub fn data(&self) -> Result<Arc<JsonValue>> {
+        self.data.get(&self.folder.join("data"))
+    }
+
+    pub fn theme_dir(&self) -> Option<PathBuf> {
+        self.project_config.theme.as_deref().map(|theme| get_theme_dir(&self.folder, theme))
+    }
+
+    /// Directories with the files served below `/assets`, project assets take precedence.
+    pub fn asset_dirs(&self) -> Vec<PathBuf> {
+        let theme_assets = self.theme_dir().map(|dir| dir.join("assets"));
+        [self.folder.join("assets")].into_iter().chain(theme_assets).collect()
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone)]
 pub struct ProjectConfig {
     pub project: Option<ProjectMetadata>,
-    pub nav: Option<Vec<NavItem>>,
-    //TODO: Markdown parser cfg
+    #[serde(default)]
+    pub nav: Vec<NavItem>,
+    /// Theme in `themes/<name>/`, its templates, assets and config are used unless the
+    /// project provides its own
+    pub theme: Option<String>,
+    #[serde(default)]
+    pub markdown: MarkdownConfig,
+    /// Fill missing dates and authors of pages from the git history
+    #[serde(default)]
+    pub git_metadata: bool,
+    /// Environment selected with `--env` or `FERROCYANIDE_ENV`
+    #[serde(skip_deserializing)]
+    pub env: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone)]
+#[serde(default)]
+pub struct MarkdownConfig {
+    pub math: MathRenderer,
+    /// Symbol of the self-link placed after each heading, e.g. `#` or `¶`
+    pub heading_anchor: Option<String>,
+    /// Number of words in a page summary if there is no `<!-- more -->` marker
+    pub summary_words: Option<usize>,
+    /// Reading speed used for the estimated reading time
+    pub words_per_minute: Option<usize>,
+    /// Weight of words in code blocks for the reading time, code is excluded by default
+    pub code_weight: Option<f64>,
+    /// Whether invalid frontmatter fails the build or is skipped with a warning
+    pub frontmatter: FrontmatterMode,
+    /// Pass raw HTML of the content through instead of omitting it, shortcodes work either way
+    pub raw_html: bool,
+    /// mermaid.js module loaded by pages with diagrams, e.g. `/assets/mermaid.esm.min.mjs` for a
+    /// copy in the assets, defaults to the jsdelivr CDN
+    pub mermaid_url: Option<String>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone)]
 pub struct ProjectMetadata {
     pub title: Option<String>,
     pub description: Option<String>,
     #[serde(rename = "rootDir")]
     pub root_dir: Option<String>,
+    /// Scheme and host of the deployed site, e.g. `https://example.com`, used by `absolute_url`
+    #[serde(rename = "baseUrl")]
+    pub base_url: Option<String>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+/// Entry of the site navigation, either `{ title, url | path, ... }` or the `Title: /url` shorthand.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone, PartialEq)]
+#[serde(try_from = "NavEntry")]
 pub struct NavItem {
-    #[serde(flatten)]
-    pub item: HashMap<String, String>,
+    pub title: String,
+    /// Site path like `/docs` or an external URL, set from `path` for content pages
+    pub url: Option<String>,
+    /// Markdown file relative to the content directory, e.g. `docs/index.md`
+    pub path: Option<String>,
+    pub icon: Option<String>,
+    /// Marks links leaving the site, URLs with a scheme are external by default
+    pub external: bool,
+    /// Entries are sorted by weight, entries with the same weight keep their order
+    pub weight: i32,
+    pub children: Vec<NavItem>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum NavEntry {
+    Item {
+        title: String,
+        url: Option<String>,
+        path: Option<String>,
+        icon: Option<String>,
+        #[serde(default)]
+        external: bool,
+        #[serde(default)]
+        weight: i32,
+        #[serde(default)]
+        children: Vec<NavItem>,
+    },
+    Shorthand(HashMap<String, String>),
+}
+
+impl TryFrom<NavEntry> for NavItem {
+    type Error = String;
+
+    fn try_from(entry: NavEntry) -> Result<Self, String> {
+        Ok(match entry {
+            NavEntry::Item {
+                title,
+                url,
+                path,
+                icon,
+                external,
+                weight,
+                children,
+            } => NavItem {
+                title,
+                url,
+                path,
+                icon,
+                external,
+                weight,
+                children,
+            },
+            NavEntry::Shorthand(item) if item.len() == 1 => {
+                let (title, url) = item.into_iter().next().unwrap_or_default();
+                NavItem {
+                    title,
+                    url: Some(url),
+                    ..Default::default()
+                }
+            }
+            NavEntry::Shorthand(_) => return Err("expected a nav entry with a `title` or a single `Title: url` pair".to_string()),
+        })
+    }
+}
+
+/// Checks that the `path` of each entry is a page of the library and sets its `url`, then
+/// sorts the entries by weight.
+pub fn resolve_nav(items: &mut [NavItem], content_dir: &Path, library: &HashMap<PathBuf, Page>) -> Result<(), String> {
+    for item in items.iter_mut() {
+        match (&item.url, &item.path) {
+            (Some(_), Some(_)) => return Err(format!("nav entry `{}` has both a `url` and a `path`", item.title)),
+            (None, Some(path)) => {
+                let path = path.trim_start_matches('/');
+                if !library.contains_key(&content_dir.join(path)) {
+                    return Err(format!("nav entry `{}` links to `{path}`, which is not a page", item.title));
+                }
+                item.url = Some(content_url(Path::new(path)));
+            }
+            (Some(url), None) => item.external |= url.contains("://"),
+            (None, None) => {}
+        }
+        resolve_nav(&mut item.children, content_dir, library)?;
+    }
+    items.sort_by_key(|item| item.weight);
+    Ok(())
 }
 
 #[cfg(test)]
@@ -63,5 +451,188 @@ project:
         } else {
             panic!("Project config should be present");
         }
+        assert_eq!(config.markdown.math, MathRenderer::MathML);
+    }
+
+    #[test]
+    fn test_read_markdown_config() {
+        let config = r#"
+markdown:
+    math: katex
+"#;
+        let config: ProjectConfig = serde_yaml::from_str(config).unwrap();
+        assert_eq!(config.markdown.math, MathRenderer::KaTeX);
+        assert_eq!(config.markdown.frontmatter, FrontmatterMode::Strict);
+
+        let config: ProjectConfig = serde_yaml::from_str("markdown:\n    frontmatter: lenient").unwrap();
+        assert_eq!(config.markdown.frontmatter, FrontmatterMode::Lenient);
+    }
+
+    #[test]
+    fn test_nav_config() {
+        let config = r#"
+nav:
+- Start: /
+- title: Guide
+  path: guide/index.md
+  weight: -1
+  children:
+  - title: Install
+    path: guide/install.md
+- title: Source
+  url: https://example.com/repo
+  icon: "⭐"
+"#;
+        let mut config: ProjectConfig = serde_yaml::from_str(config).unwrap();
+        let library = ["index.md", "guide/index.md", "guide/install.md"]
+            .into_iter()
+            .map(|path| (Path::new("content").join(path), Page::default()))
+            .collect::<HashMap<_, _>>();
+        resolve_nav(&mut config.nav, Path::new("content"), &library).unwrap();
+
+        let titles = config.nav.iter().map(|item| item.title.as_str()).collect::<Vec<_>>();
+        assert_eq!(titles, ["Guide", "Start", "Source"]);
+        assert_eq!(config.nav[0].url.as_deref(), Some("/guide/"));
+        assert_eq!(config.nav[0].children[0].url.as_deref(), Some("/guide/install"));
+        assert_eq!(config.nav[1].url.as_deref(), Some("/"));
+        assert!(config.nav[2].external && !config.nav[1].external);
+
+        let mut missing: ProjectConfig = serde_yaml::from_str("nav:\n- title: Gone\n  path: gone.md").unwrap();
+        let err = resolve_nav(&mut missing.nav, Path::new("content"), &library).unwrap_err();
+        assert_eq!(err, "nav entry `Gone` links to `gone.md`, which is not a page");
+        assert!(serde_yaml::from_str::<ProjectConfig>("nav:\n- titel: Typo\n  url: /typo").is_err());
+    }
+
+    #[test]
+    fn test_theme_config_defaults() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path().to_path_buf();
+        let theme_dir = get_theme_dir(&folder, "docs");
+        fs::create_dir_all(&theme_dir).unwrap();
+        fs::write(
+            get_config_path(&theme_dir),
+            "project:\n    title: Theme\n    description: From the theme\nmarkdown:\n    math: katex",
+        )
+        .unwrap();
+        fs::write(get_config_path(&folder), "theme: docs\nproject:\n    title: Project").unwrap();
+
+        let config = read_project_config(&folder, None).unwrap();
+        let project = config.project.unwrap();
+        assert_eq!(project.title.as_deref(), Some("Project"));
+        assert_eq!(project.description.as_deref(), Some("From the theme"));
+        assert_eq!(config.markdown.math, MathRenderer::KaTeX);
+
+        fs::write(get_config_path(&folder), "theme: missing").unwrap();
+        let err = read_project_config(&folder, None).unwrap_err();
+        assert!(err.chain().nth(1).unwrap().to_string().starts_with("theme `missing` does not exist"));
+    }
+
+    #[test]
+    fn test_env_config_overlay() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path().to_path_buf();
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(
+            get_config_path(&folder),
+            "project:\n    title: Site\n    rootDir: /site\nmarkdown:\n    math: katex",
+        )
+        .unwrap();
+        fs::write(
+            get_env_config_path(&folder, "staging"),
+            "project:\n    rootDir: /staging\n    baseUrl: https://staging.example.com",
+        )
+        .unwrap();
+
+        let config = read_project_config(&folder, Some("staging")).unwrap();
+        let project = config.project.as_ref().unwrap();
+        assert_eq!(project.title.as_deref(), Some("Site"));
+        assert_eq!(project.root_dir.as_deref(), Some("/staging"));
+        assert_eq!(project.base_url.as_deref(), Some("https://staging.example.com"));
+        assert_eq!(config.markdown.math, MathRenderer::KaTeX);
+        assert_eq!(config.env.as_deref(), Some("staging"));
+
+        let dump = dump_config(&folder, Some("staging")).unwrap();
+        assert!(dump.contains("rootDir: /staging"));
+        assert!(read_project_config(&folder, Some("production")).is_err());
+    }
+
+    #[test]
+    fn test_invalid_config_location() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path().to_path_buf();
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(get_config_path(&folder), "project:\n    title: [Site]").unwrap();
+        let err = read_project_config(&folder, None).unwrap_err();
+        assert_eq!(err.to_string(), format!("Invalid config {}:2:12", get_config_path(&folder).display()));
+        assert!(err.chain().nth(1).unwrap().to_string().starts_with("project.title: invalid type"));
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let config = r#"
+project:
+    title: Site
+    rootdir: /site
+markdwn:
+    math: katex
+nav:
+- Start: /
+- title: Guide
+  url: /guide
+  childs:
+  - title: Install
+    url: /install
+    wieght: 1
+git_metadata: true
+colors: dark
+"#;
+        let config: Mapping = serde_yaml::from_str(config).unwrap();
+        assert_eq!(
+            unknown_keys(&config),
+            [
+                "unknown key `project.rootdir`, did you mean `rootDir`?",
+                "unknown key `markdwn`, did you mean `markdown`?",
+                "unknown key `nav[1].childs`, did you mean `children`?",
+                "unknown key `colors`",
+            ]
+        );
+        assert!(unknown_keys(&serde_yaml::from_str("nav:\n- title: Guide\n  children:\n  - Install: /install").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let lookup = |name: &str| match name {
+            "HOST" => Some("example.com".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        let mut value: Value =
+            serde_yaml::from_str("url: https://${HOST}/${PREFIX:-docs}\nids: [\"${EMPTY:-none}\", 42]\nplain: no variables").unwrap();
+        interpolate(&mut value, &lookup).unwrap();
+        let expected: Value = serde_yaml::from_str("url: https://example.com/docs\nids: [none, 42]\nplain: no variables").unwrap();
+        assert_eq!(value, expected);
+
+        let mut missing = Value::String("${MISSING}".to_string());
+        assert_eq!(
+            interpolate(&mut missing, &lookup).unwrap_err(),
+            "environment variable `MISSING` is not set"
+        );
+        let mut unclosed = Value::String("${HOST".to_string());
+        assert!(interpolate(&mut unclosed, &lookup).is_err());
+    }
+
+    #[test]
+    fn test_interpolate_typed_values() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path().to_path_buf();
+        fs::write(
+            get_config_path(&folder),
+            "project:\n    title: ${CRATE_TEST_TITLE:-2024}\nmarkdown:\n    summary_words: ${CRATE_TEST_WORDS:-30}\ngit_metadata: ${CRATE_TEST_GIT:-true}",
+        )
+        .unwrap();
+        let config = read_project_config(&folder, None).unwrap();
+        assert_eq!(config.markdown.summary_words, Some(30));
+        assert!(config.git_metadata);
+        assert_eq!(config.project.unwrap().title.as_deref(), Some("2024"));
     }
 }
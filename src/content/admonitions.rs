@@ -0,0 +1,204 @@
+use super::markdown::{MarkdownError, verbatim_ranges};
+use crate::templates::TEMPLATES;
+use comrak::{
+    ComrakPlugins, Options, format_html_with_plugins,
+    nodes::{AstNode, NodeHtmlBlock, NodeValue},
+};
+use tera::{Context, Tera};
+
+const START_MARKER: &str = "<!-- admonition ";
+const END_MARKER: &str = "<!-- /admonition -->";
+
+/// Rewrites `:::kind Optional title` ... `:::` containers into HTML comment markers, which are
+/// picked up as html blocks by comrak and grouped by [`render_admonitions`]. Line numbers are kept
+/// intact so source positions still point into the original file.
+pub fn preprocess_containers(content: &str) -> String {
+    let verbatim = verbatim_ranges(content);
+    let mut out = String::with_capacity(content.len());
+    let mut depth = 0;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        if verbatim.iter().any(|range| range.contains(&start)) {
+            out.push_str(line);
+            continue;
+        }
+
+        let trimmed = line.trim();
+        let indent = &line[..line.len() - line.trim_start().len()];
+        if let Some(rest) = trimmed.strip_prefix(":::") {
+            let rest = rest.trim_start_matches(':').trim();
+            if rest.is_empty() && depth > 0 {
+                depth -= 1;
+                out.push_str(&format!("{indent}{END_MARKER}\n"));
+                continue;
+            }
+            let (kind, title) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if !kind.is_empty() && kind.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+                depth += 1;
+                let title = title.trim().replace("-->", "--&gt;");
+                out.push_str(&format!("{indent}{START_MARKER}{} {title} -->\n", kind.to_lowercase()));
+                continue;
+            }
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+/// Replaces GitHub-style alerts and `:::` containers with `admonition.html`, falling back to
+/// `__builtins/admonition.html`.
+pub(crate) fn render_admonitions<'a>(
+    root: &'a AstNode<'a>,
+    options: &Options,
+    plugins: &ComrakPlugins,
+    tera: Option<&Tera>,
+    data: Option<&serde_json::Value>,
+) -> Result<(), MarkdownError> {
+    group_containers(root);
+
+    let tera = tera.unwrap_or(&TEMPLATES);
+    let template = if tera.get_template_names().any(|name| name == "admonition.html") {
+        "admonition.html"
+    } else {
+        "__builtins/admonition.html"
+    };
+
+    // reversed pre-order visits nested admonitions before their parents
+    let nodes = root.descendants().collect::<Vec<_>>();
+    for node in nodes.into_iter().rev() {
+        let (kind, title) = match &node.data.borrow().value {
+            NodeValue::Alert(alert) => (
+                format!("{:?}", alert.alert_type).to_lowercase(),
+                alert.title.clone().unwrap_or_else(|| alert.alert_type.default_title()),
+            ),
+            NodeValue::HtmlBlock(html) if html.literal.starts_with(START_MARKER) => parse_marker(&html.literal),
+            _ => continue,
+        };
+
+        let mut body = vec![];
+        for child in node.children() {
+            format_html_with_plugins(child, options, &mut body, plugins)?;
+        }
+
+        let mut context = Context::new();
+        if let Some(data) = data {
+            context.insert("data", data);
+        }
+        context.insert("kind", &kind);
+        context.insert("title", &title);
+        context.insert("body", &String::from_utf8_lossy(&body));
+        let html = tera.render(template, &context).map_err(|source| MarkdownError::Template {
+            message: format!("Failed to render admonition template `{template}`"),
+            source,
+        })?;
+
+        for child in node.children().collect::<Vec<_>>() {
+            child.detach();
+        }
+        node.data.borrow_mut().value = NodeValue::HtmlBlock(NodeHtmlBlock {
+            block_type: 6,
+            literal: format!("{}\n", html.trim_end()),
+        });
+    }
+    Ok(())
+}
+
+/// Moves all siblings between a start marker and its matching end marker into the start marker.
+fn group_containers<'a>(root: &'a AstNode<'a>) {
+    let starts = root.descendants().filter(|node| is_marker(node, true)).collect::<Vec<_>>();
+    for start in starts {
+        let mut depth = 0;
+        while let Some(sibling) = start.next_sibling() {
+            if is_marker(sibling, true) {
+                depth += 1;
+            } else if is_marker(sibling, false) {
+                if depth == 0 {
+                    sibling.detach();
+                    break;
+                }
+                depth -= 1;
+            }
+            start.append(sibling);
+        }
+    }
+}
+
+fn is_marker<'a>(node: &'a AstNode<'a>, start: bool) -> bool {
+    match &node.data.borrow().value {
+        NodeValue::HtmlBlock(html) if start => html.literal.starts_with(START_MARKER),
+        NodeValue::HtmlBlock(html) => html.literal.trim_end() == END_MARKER,
+        _ => false,
+    }
+}
+
+fn parse_marker(marker: &str) -> (String, String) {
+    let inner = marker.trim_end().trim_start_matches(START_MARKER).trim_end_matches("-->").trim();
+    let (kind, title) = inner.split_once(' ').unwrap_or((inner, ""));
+    let title = match title.trim() {
+        "" => {
+            let mut chars = kind.chars();
+            chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+        }
+        title => title.replace("--&gt;", "-->"),
+    };
+    (kind.to_string(), title)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::content::{markdown::RenderContext, page::Page};
+
+    #[test]
+    fn test_github_alert() {
+        let page = Page::from_string("> [!WARNING]\n> Do **not** do this").unwrap();
+        assert!(page.content.contains("<aside class=\"admonition warning\">"));
+        assert!(page.content.contains("<p class=\"admonition-title\">Warning</p>"));
+        assert!(page.content.contains("<p>Do <strong>not</strong> do this</p>"));
+    }
+
+    #[test]
+    fn test_container() {
+        let page = Page::from_string(":::tip Pro tip\nUse `serve` while writing\n:::\n\nAfter").unwrap();
+        assert!(page.content.contains("<aside class=\"admonition tip\">"));
+        assert!(page.content.contains("<p class=\"admonition-title\">Pro tip</p>"));
+        assert!(page.content.contains("<p>Use <code>serve</code> while writing</p>"));
+        assert!(page.content.ends_with("</aside>\n<p>After</p>\n"));
+    }
+
+    #[test]
+    fn test_data_in_templates() {
+        let mut tera = tera::Tera::default();
+        tera.add_raw_templates(vec![
+            ("admonition.html", "<aside>{{ data.site.name }}: {{ body | safe }}</aside>"),
+            ("shortcodes/team.html", "{{ data.team | join(sep=', ') }}"),
+        ])
+        .unwrap();
+        let data = serde_json::json!({ "site": { "name": "Docs" }, "team": ["Alice", "Bob"] });
+        let ctx = RenderContext {
+            tera: Some(&tera),
+            data: Some(&data),
+            ..Default::default()
+        };
+        let page = Page::render(":::note\nBy {{ team() }}\n:::", &ctx).unwrap();
+        assert_eq!(page.content, "<aside>Docs: <p>By Alice, Bob</p>\n</aside>\n");
+    }
+
+    #[test]
+    fn test_nested_containers() {
+        let page = Page::from_string(":::danger\nOuter\n:::note\nInner\n:::\n:::").unwrap();
+        let outer = page.content.find("admonition danger").unwrap();
+        let inner = page.content.find("admonition note").unwrap();
+        assert!(outer < inner);
+        assert!(page.content.contains("<p class=\"admonition-title\">Danger</p>"));
+        assert_eq!(page.content.matches("</aside>").count(), 2);
+    }
+
+    #[test]
+    fn test_container_in_code_block() {
+        let page = Page::from_string("```\n:::tip\n```").unwrap();
+        assert!(!page.content.contains("admonition"));
+    }
+}
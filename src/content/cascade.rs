@@ -0,0 +1,95 @@
+use crate::{Error, Result};
+use serde_yaml::{Mapping, Value};
+use std::{fs, path::Path};
+
+const CASCADE_KEY: &str = "cascade";
+
+/// Settings of `dir` merged from the `<name>/<name>.yaml` files of every directory from
+/// `content_dir` down to `dir`, the nearest directory wins. Values below a `cascade` key
+/// only apply to subdirectories of the directory defining them.
+pub fn dir_config(content_dir: &Path, dir: &Path) -> Result<Mapping> {
+    let mut dirs = dir
+        .ancestors()
+        .take_while(|ancestor| ancestor.starts_with(content_dir))
+        .collect::<Vec<_>>();
+    if dirs.is_empty() {
+        dirs.push(dir);
+    }
+
+    let mut config = Mapping::new();
+    for ancestor in dirs.into_iter().rev() {
+        let Some(name) = ancestor.file_name() else {
+            continue;
+        };
+        let file = ancestor.join(format!("{}.yaml", name.to_string_lossy()));
+        if !file.exists() {
+            continue;
+        }
+
+        let source = fs::read_to_string(&file).map_err(Error::io(&file))?;
+        let invalid = |source| Error::Config { path: file.clone(), source };
+        let mut settings = match serde_yaml::from_str(&source).map_err(|err| invalid(Box::new(err)))? {
+            Value::Mapping(settings) => settings,
+            Value::Null => Mapping::new(),
+            _ => return Err(invalid("expected a mapping".into())),
+        };
+        let cascade = settings.remove(CASCADE_KEY);
+
+        merge(&mut config, settings);
+        if ancestor != dir
+            && let Some(Value::Mapping(cascade)) = cascade
+        {
+            merge(&mut config, cascade);
+        }
+    }
+    Ok(config)
+}
+
+/// Deep merges `overlay` into `base`, nested mappings are merged and all other values replaced.
+pub fn merge(base: &mut Mapping, overlay: Mapping) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Mapping(base)), Value::Mapping(value)) => merge(base, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(yaml: &str) -> Mapping {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = mapping("layout: doc.html\nsidebar: {show: true, title: Docs}\ntags: [a]");
+        merge(&mut base, mapping("sidebar: {title: Guide}\ntags: [b]"));
+        assert_eq!(base, mapping("layout: doc.html\nsidebar: {show: true, title: Guide}\ntags: [b]"));
+    }
+
+    #[test]
+    fn test_dir_config() {
+        let temp = tempfile::tempdir().unwrap();
+        let content = temp.path().join("content");
+        let guide = content.join("docs").join("guide");
+        fs::create_dir_all(&guide).unwrap();
+        fs::write(content.join("content.yaml"), "toc_depth: 2\nsidebar: {show: false}").unwrap();
+        fs::write(
+            content.join("docs").join("docs.yaml"),
+            "layout: doc.html\nsidebar: {show: true, title: Docs}\ncascade: {layout: guide.html}",
+        )
+        .unwrap();
+        fs::write(guide.join("guide.yaml"), "sidebar: {title: Guide}").unwrap();
+
+        let docs = dir_config(&content, &content.join("docs")).unwrap();
+        assert_eq!(docs, mapping("toc_depth: 2\nsidebar: {show: true, title: Docs}\nlayout: doc.html"));
+
+        let guide = dir_config(&content, &guide).unwrap();
+        assert_eq!(guide, mapping("toc_depth: 2\nsidebar: {show: true, title: Guide}\nlayout: guide.html"));
+    }
+}
@@ -0,0 +1,135 @@
+use super::{escape, markdown::MarkdownError, stable_hash};
+use comrak::nodes::{AstNode, NodeHtmlBlock, NodeValue};
+use layout::{backends::svg::SVGWriter, gv};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::Mutex,
+};
+
+pub const DEFAULT_MERMAID_URL: &str = "https://cdn.jsdelivr.net/npm/mermaid@11/dist/mermaid.esm.min.mjs";
+
+/// Module script loading mermaid.js from `url`, e.g. a copy in the assets of the site.
+pub fn mermaid_script(url: &str) -> String {
+    format!("<script type=\"module\">\nimport mermaid from \"{url}\";\nmermaid.initialize({{ startOnLoad: true }});\n</script>\n")
+}
+
+static CACHE: Lazy<Mutex<HashMap<u64, String>>> = Lazy::new(Default::default);
+
+/// Replaces ```` ```dot ```` fences with inline SVG and ```` ```mermaid ```` fences with markup for
+/// mermaid.js. Returns whether the document needs [`mermaid_script`].
+/// Rendered DOT diagrams are cached by content hash in memory and in `cache_dir`.
+pub(crate) fn render_diagrams<'a>(root: &'a AstNode<'a>, cache_dir: Option<&Path>) -> Result<bool, MarkdownError> {
+    let mut has_mermaid = false;
+
+    for node in root.descendants() {
+        let mut data = node.data.borrow_mut();
+        let line = data.sourcepos.start.line;
+        let html = match &data.value {
+            NodeValue::CodeBlock(code) if code.info == "mermaid" => {
+                has_mermaid = true;
+                format!("<pre class=\"mermaid\">{}</pre>", escape(code.literal.trim_end()))
+            }
+            NodeValue::CodeBlock(code) if code.info == "dot" || code.info == "graphviz" => {
+                let svg = cached(&code.literal, cache_dir, render_dot).map_err(|message| MarkdownError::Diagram { line, message })?;
+                format!("<figure class=\"diagram\">{svg}</figure>")
+            }
+            _ => continue,
+        };
+        data.value = NodeValue::HtmlBlock(NodeHtmlBlock {
+            block_type: 6,
+            literal: format!("{html}\n"),
+        });
+    }
+
+    Ok(has_mermaid)
+}
+
+fn cached(source: &str, cache_dir: Option<&Path>, render: fn(&str) -> Result<String, String>) -> Result<String, String> {
+    let key = stable_hash(format!("{}\0{source}", env!("CARGO_PKG_VERSION")).as_bytes());
+
+    if let Some(svg) = CACHE.lock().unwrap().get(&key) {
+        return Ok(svg.clone());
+    }
+
+    let file = cache_dir.map(|dir| dir.join(format!("{key:016x}.svg")));
+    let svg = match file.as_ref().and_then(|file| fs::read_to_string(file).ok()) {
+        Some(svg) => svg,
+        None => {
+            let svg = render(source)?;
+            if let Some(file) = &file {
+                // the cache is an optimization only, failing to write it is not an error
+                fs::create_dir_all(file.parent().unwrap()).and_then(|_| fs::write(file, &svg)).ok();
+            }
+            svg
+        }
+    };
+
+    CACHE.lock().unwrap().insert(key, svg.clone());
+    Ok(svg)
+}
+
+fn render_dot(source: &str) -> Result<String, String> {
+    let graph = gv::DotParser::new(source).process()?;
+
+    // layout-rs panics on some unsupported graphs instead of returning an error
+    let svg = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut builder = gv::GraphBuilder::new();
+        builder.visit_graph(&graph);
+        let mut visual = builder.get();
+        let mut svg = SVGWriter::new();
+        visual.do_it(false, false, false, &mut svg);
+        svg.finalize()
+    }))
+    .map_err(|_| "Failed to lay out graph")?;
+
+    // drop the xml declaration, the svg is embedded into html
+    let start = svg.find("<svg").unwrap_or(0);
+    Ok(svg[start..].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::MarkdownConfig,
+        content::{markdown::RenderContext, page::Page},
+    };
+
+    #[test]
+    fn test_dot_diagram() {
+        let page = Page::from_string("```dot\ndigraph { a -> b; }\n```").unwrap();
+        assert!(page.content.starts_with("<figure class=\"diagram\"><svg"));
+        assert!(!page.content.contains("<?xml"));
+        assert!(!page.content.contains("<script"));
+    }
+
+    #[test]
+    fn test_invalid_dot_diagram() {
+        let err = Page::from_string("# Graph\n\n```dot\ndigraph { a -> ; }\n```").unwrap_err();
+        assert_eq!(err.chain().nth(1).unwrap().to_string(), "Invalid dot diagram at line 3: port");
+    }
+
+    #[test]
+    fn test_mermaid_diagram() {
+        let content = "```mermaid\ngraph TD\n  A --> B\n```\n\n```mermaid\ngraph LR\n  C --> D\n```";
+        let page = Page::from_string(content).unwrap();
+        assert!(page.content.contains("<pre class=\"mermaid\">graph TD\n  A --&gt; B</pre>"));
+        assert_eq!(page.content.matches("import mermaid").count(), 1);
+        assert!(page.content.contains(DEFAULT_MERMAID_URL));
+
+        let ctx = RenderContext {
+            config: MarkdownConfig {
+                mermaid_url: Some("/assets/mermaid.esm.min.mjs".to_string()),
+                ..Default::default()
+            },
+            url_prefix: "/docs".to_string(),
+            ..Default::default()
+        };
+        let page = Page::render(content, &ctx).unwrap();
+        assert!(page.content.contains("import mermaid from \"/docs/assets/mermaid.esm.min.mjs\";"));
+    }
+}
@@ -1,6 +1,11 @@
-use serde::{Deserialize, Deserializer, Serialize};
-use serde_yaml::Value;
-use std::collections::HashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, de::IgnoredAny};
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
 use time::{self, Date, PrimitiveDateTime, Time, format_description::well_known::Rfc3339, macros::format_description};
 
 #[derive(Debug, Clone, Serialize, Default, Deserialize)]
@@ -15,48 +20,335 @@ pub struct Frontmatter {
     #[serde(deserialize_with = "deserialize_datetime")]
     pub updated_at: Option<PrimitiveDateTime>,
     pub authors: Vec<String>,
+    /// Number of heading levels shown in the table of contents
+    pub toc_depth: Option<u8>,
+    /// Highest heading level shown in the table of contents, e.g. `2` to skip the page title
+    pub toc_min_level: Option<u8>,
     pub extra: HashMap<String, Value>,
 }
 impl Frontmatter {
-    pub fn read(markdown: &str) -> Option<Self> {
-        let mut parts = markdown.splitn(3, "---");
-        parts.next()?;
-        let frontmatter = parts.next()?;
-        serde_yaml::from_str(frontmatter).ok()
+    /// Reads the YAML (`---`), TOML (`+++`) or JSON (`{ ... }` or `;;;`) frontmatter at the start of `markdown`.
+    pub fn read(markdown: &str) -> Result<Option<Self>, FrontmatterError> {
+        RawFrontmatter::find(markdown).map(|raw| raw.parse()).transpose()
+    }
+
+    /// Like [`Frontmatter::read`], but invalid keys are skipped and returned as errors. If the
+    /// frontmatter can't be parsed at all, the default is returned instead.
+    pub fn read_lenient(markdown: &str) -> (Option<Self>, Vec<FrontmatterError>) {
+        let Some(raw) = RawFrontmatter::find(markdown) else {
+            return (None, vec![]);
+        };
+        match raw.parse_lenient() {
+            Ok((frontmatter, errors)) => (Some(frontmatter), errors),
+            Err(err) => (Some(Frontmatter::default()), vec![err]),
+        }
+    }
+
+    /// Fields set in the frontmatter, the final layer over the directory settings.
+    pub fn overrides(&self) -> Mapping {
+        let Ok(Value::Mapping(mut fields)) = serde_yaml::to_value(self) else {
+            return Mapping::new();
+        };
+        fields.retain(|_, value| match value {
+            Value::Null => false,
+            Value::Sequence(sequence) => !sequence.is_empty(),
+            Value::Mapping(mapping) => !mapping.is_empty(),
+            _ => true,
+        });
+        fields
     }
 }
 
-fn deserialize_datetime<'de, D>(deserializer: D) -> Result<Option<PrimitiveDateTime>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s: Option<&str> = Option::deserialize(deserializer)?;
-    if let Some(s) = s {
-        let formats = [
-            format_description!("[year]-[month]-[day] [hour]:[minute]"),
-            format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]"),
-            format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z"),
-        ];
-        for fmt in &formats {
-            if let Ok(dt) = PrimitiveDateTime::parse(s, fmt) {
-                return Ok(Some(dt));
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FrontmatterMode {
+    /// Invalid frontmatter fails the build
+    #[default]
+    Strict,
+    /// Invalid keys are reported as warnings and skipped
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Yaml => "YAML",
+            Format::Toml => "TOML",
+            Format::Json => "JSON",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FrontmatterError {
+    pub path: Option<PathBuf>,
+    pub format: Format,
+    /// Line in the markdown document
+    pub line: usize,
+    pub column: Option<usize>,
+    /// Offending key, `None` for syntax errors
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl FrontmatterError {
+    pub fn with_path(self, path: Option<&Path>) -> Self {
+        FrontmatterError {
+            path: path.map(Path::to_path_buf),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for FrontmatterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid {} frontmatter", self.format)?;
+        if let Some(path) = &self.path {
+            write!(f, " in {}", path.display())?;
+        }
+        write!(f, " at line {}", self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ", column {column}")?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, " in `{key}`")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for FrontmatterError {}
+
+const FENCES: [(&str, Format); 3] = [("---", Format::Yaml), ("+++", Format::Toml), (";;;", Format::Json)];
+
+/// Unparsed frontmatter block at the start of a markdown document.
+#[derive(Debug, Clone, Copy)]
+pub struct RawFrontmatter<'a> {
+    pub format: Format,
+    pub source: &'a str,
+    /// Line of the document on which `source` starts
+    pub line: usize,
+    /// Byte offset of the content following the frontmatter
+    pub end: usize,
+}
+
+impl<'a> RawFrontmatter<'a> {
+    pub fn find(markdown: &'a str) -> Option<Self> {
+        let start = markdown.len() - markdown.trim_start().len();
+        let rest = &markdown[start..];
+        let line = markdown[..start].matches('\n').count() + 1;
+
+        // unfenced json is only frontmatter if it is a complete object, text like `{Braces} are
+        // fun` or a leading shortcode is content
+        if rest.starts_with('{') {
+            let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<IgnoredAny>();
+            let Some(Ok(_)) = stream.next() else {
+                return None;
+            };
+            let end = start + stream.byte_offset();
+            return Some(Self {
+                format: Format::Json,
+                source: &markdown[start..end],
+                line,
+                end,
+            });
+        }
+
+        let fence_line = rest.split_inclusive('\n').next()?;
+        let (fence, format) = FENCES.into_iter().find(|(fence, _)| fence_line.trim_end() == *fence)?;
+        let source_start = start + fence_line.len();
+        let mut offset = source_start;
+        for content_line in markdown[source_start..].split_inclusive('\n') {
+            if content_line.trim() == fence {
+                return Some(Self {
+                    format,
+                    source: &markdown[source_start..offset],
+                    line: line + 1,
+                    end: offset + content_line.len(),
+                });
             }
+            offset += content_line.len();
+        }
+        None
+    }
+
+    /// Fails on the first invalid key.
+    pub fn parse(&self) -> Result<Frontmatter, FrontmatterError> {
+        let mapping = self.parse_mapping()?;
+        match self.invalid_keys(&mapping).into_iter().next() {
+            Some(err) => Err(err),
+            None => self.deserialize(mapping),
         }
+    }
+
+    /// Skips invalid keys and returns their errors along with the remaining fields.
+    pub fn parse_lenient(&self) -> Result<(Frontmatter, Vec<FrontmatterError>), FrontmatterError> {
+        let mut mapping = self.parse_mapping()?;
+        let errors = self.invalid_keys(&mapping);
+        for err in &errors {
+            mapping.remove(err.key.as_deref().unwrap_or_default());
+        }
+        Ok((self.deserialize(mapping)?, errors))
+    }
+
+    /// Parses the source into a format independent mapping, which is checked key by key.
+    fn parse_mapping(&self) -> Result<Mapping, FrontmatterError> {
+        if self.source.trim().is_empty() {
+            return Ok(Mapping::new());
+        }
+
+        let result = match self.format {
+            Format::Yaml => serde_yaml::from_str(self.source).map_err(|e| {
+                let location = e.location().map(|location| (location.line(), location.column()));
+                (location, self.strip_location(e.to_string()))
+            }),
+            Format::Toml => toml::from_str(self.source).map_err(|e| {
+                let location = e.span().map(|span| {
+                    let before = &self.source[..span.start];
+                    (before.matches('\n').count() + 1, span.start - before.rfind('\n').map_or(0, |i| i + 1) + 1)
+                });
+                (location, e.message().to_string())
+            }),
+            Format::Json => serde_json::from_str(self.source).map_err(|e| (Some((e.line(), e.column())), self.strip_location(e.to_string()))),
+        };
+
+        match result {
+            Ok(Value::Mapping(mapping)) => Ok(mapping),
+            Ok(_) => Err(self.error((1, None), None, "expected a mapping of keys and values".to_string())),
+            Err((location, message)) => {
+                let (line, column) = location.unzip();
+                Err(self.error((line.unwrap_or(1), column), None, message))
+            }
+        }
+    }
+
+    fn invalid_keys(&self, mapping: &Mapping) -> Vec<FrontmatterError> {
+        let mut errors = mapping
+            .iter()
+            .filter_map(|(key, value)| {
+                let single = Mapping::from_iter([(key.clone(), value.clone())]);
+                let err = serde_yaml::from_value::<Frontmatter>(Value::Mapping(single)).err()?;
+                let key = key.as_str().map(str::to_string).unwrap_or_else(|| format!("{key:?}"));
+                let (line, column) = self.key_location(&key).unzip();
+                Some(self.error((line.unwrap_or(1), column), Some(key), self.strip_location(err.to_string())))
+            })
+            .collect::<Vec<_>>();
+        errors.sort_by_key(|err| err.line);
+        errors
+    }
+
+    fn deserialize(&self, mapping: Mapping) -> Result<Frontmatter, FrontmatterError> {
+        serde_yaml::from_value(Value::Mapping(mapping)).map_err(|e| self.error((1, None), None, self.strip_location(e.to_string())))
+    }
 
-        if let Ok(odt) = time::OffsetDateTime::parse(s, &Rfc3339) {
-            return Ok(Some(odt.date().with_time(odt.time())));
+    /// Line and column of `key` in the source, found by the first line defining it.
+    fn key_location(&self, key: &str) -> Option<(usize, usize)> {
+        let quoted = format!("\"{key}\"");
+        self.source.lines().enumerate().find_map(|(index, line)| {
+            let trimmed = line.trim_start();
+            let rest = trimmed.strip_prefix(key).or_else(|| trimmed.strip_prefix(&quoted))?;
+            let rest = rest.trim_start();
+            (rest.starts_with(':') || rest.starts_with('=')).then_some((index + 1, line.len() - trimmed.len() + 1))
+        })
+    }
+
+    /// Removes the first ` at line X column Y` from the message, it is reported as the location of
+    /// the error. Later ones like `while parsing a flow sequence at line 1 column 8` are relative
+    /// to the source and translated to lines of the document.
+    fn strip_location(&self, message: String) -> String {
+        let mut parts = message.split(" at line ");
+        let mut stripped = parts.next().unwrap_or_default().to_string();
+        for (index, part) in parts.enumerate() {
+            let digits = |text: &str| text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+            let Ok(line) = part[..digits(part)].parse::<usize>() else {
+                stripped.push_str(" at line ");
+                stripped.push_str(part);
+                continue;
+            };
+            let rest = &part[digits(part)..];
+            let (column, rest) = match rest.strip_prefix(" column ") {
+                Some(rest) => (Some(&rest[..digits(rest)]), &rest[digits(rest)..]),
+                None => (None, rest),
+            };
+            if index > 0 {
+                stripped.push_str(&format!(" at line {}", self.line + line - 1));
+                if let Some(column) = column {
+                    stripped.push_str(&format!(", column {column}"));
+                }
+            }
+            stripped.push_str(rest);
         }
+        stripped
+    }
 
-        if let Ok(date) = Date::parse(s, format_description!("[year]-[month]-[day]")) {
-            return Ok(Some(PrimitiveDateTime::new(date, Time::MIDNIGHT)));
+    /// Creates an error from a location relative to the source.
+    fn error(&self, (line, column): (usize, Option<usize>), key: Option<String>, message: String) -> FrontmatterError {
+        FrontmatterError {
+            path: None,
+            format: self.format,
+            line: self.line + line - 1,
+            column,
+            key,
+            message,
         }
+    }
+}
+
+/// Dates are strings in YAML and JSON, TOML has a native datetime type.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DateValue {
+    String(String),
+    Toml(toml::value::Datetime),
+}
 
-        Err(serde::de::Error::custom(format!("Unrecognized datetime format: {}", s)))
+fn deserialize_datetime<'de, D>(deserializer: D) -> Result<Option<PrimitiveDateTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = match Option::<DateValue>::deserialize(deserializer)? {
+        Some(DateValue::String(s)) => Some(s),
+        Some(DateValue::Toml(datetime)) => Some(datetime.to_string()),
+        None => None,
+    };
+    if let Some(s) = s.as_deref() {
+        parse_datetime(s)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("Unrecognized datetime format: {}", s)))
     } else {
         Ok(None)
     }
 }
 
+/// Parses the date formats supported in frontmatter, a date without time is at midnight.
+pub fn parse_datetime(s: &str) -> Option<PrimitiveDateTime> {
+    let formats = [
+        format_description!("[year]-[month]-[day] [hour]:[minute]"),
+        format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]"),
+        format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z"),
+    ];
+    for fmt in &formats {
+        if let Ok(dt) = PrimitiveDateTime::parse(s, fmt) {
+            return Some(dt);
+        }
+    }
+
+    if let Ok(odt) = time::OffsetDateTime::parse(s, &Rfc3339) {
+        return Some(odt.date().with_time(odt.time()));
+    }
+
+    Date::parse(s, format_description!("[year]-[month]-[day]"))
+        .ok()
+        .map(|date| PrimitiveDateTime::new(date, Time::MIDNIGHT))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,11 +384,98 @@ mod tests {
         # This is a test markdown content
         "#;
 
-        let frontmatter = Frontmatter::read(yaml).unwrap();
+        let frontmatter = Frontmatter::read(yaml).unwrap().unwrap();
         assert_eq!(frontmatter.title, Some("Test Title".to_string()));
         assert_eq!(frontmatter.description, Some("Test Description".to_string()));
     }
 
+    #[test]
+    fn test_read_toml_frontmatter() {
+        let markdown =
+            "+++\ntitle = \"Test Title\"\ncreated_at = 2025-04-30T13:45:30Z\nupdated_at = \"2025-05-01\"\nauthors = [\"Alice\"]\n+++\n# Content";
+        let frontmatter = Frontmatter::read(markdown).unwrap().unwrap();
+        assert_eq!(frontmatter.title, Some("Test Title".to_string()));
+        assert_eq!(frontmatter.created_at, Some(datetime!(2025-04-30 13:45:30)));
+        assert_eq!(frontmatter.updated_at, Some(datetime!(2025-05-01 0:00)));
+        assert_eq!(frontmatter.authors, ["Alice"]);
+    }
+
+    #[test]
+    fn test_read_json_frontmatter() {
+        let markdown = "{\n  \"title\": \"Test Title\",\n  \"created_at\": \"2025-04-30 13:45\"\n}\n# Content";
+        let raw = RawFrontmatter::find(markdown).unwrap();
+        assert_eq!(raw.format, Format::Json);
+        assert_eq!(&markdown[raw.end..], "\n# Content");
+        let frontmatter = raw.parse().unwrap();
+        assert_eq!(frontmatter.title, Some("Test Title".to_string()));
+        assert_eq!(frontmatter.created_at, Some(datetime!(2025-04-30 13:45)));
+
+        let fenced = Frontmatter::read(";;;\n{\"title\": \"Fenced\"}\n;;;\n# Content").unwrap().unwrap();
+        assert_eq!(fenced.title, Some("Fenced".to_string()));
+        assert!(Frontmatter::read("{{ shortcode() }}").unwrap().is_none());
+        assert!(Frontmatter::read("{Braces} are fun").unwrap().is_none());
+        assert!(Frontmatter::read("{\"title\": \"Unclosed\"\n# Content").unwrap().is_none());
+
+        let (frontmatter, errors) = Frontmatter::read_lenient("{Braces} are fun");
+        assert!(frontmatter.is_none() && errors.is_empty());
+        let page = crate::content::page::Page::from_string("{Braces} are fun").unwrap();
+        assert_eq!(page.content, "<p>{Braces} are fun</p>\n");
+    }
+
+    #[test]
+    fn test_frontmatter_error_line() {
+        let err = Frontmatter::read("---\ntitle: Test\nauthors: [Alice]]\n---").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid YAML frontmatter at line 3, column 17: did not find expected key, while parsing a block mapping"
+        );
+        assert_eq!(err.key, None);
+
+        let err = Frontmatter::read("---\ntitle: Test\nauthors: [Alice\ntags: [docs]\n---").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid YAML frontmatter at line 4, column 5: did not find expected ',' or ']', \
+             while parsing a flow sequence at line 3, column 10"
+        );
+
+        let err = Frontmatter::read("+++\ntitle = \"Test\"\nauthors = 1\n+++").unwrap_err();
+        assert!(
+            err.to_string().starts_with("Invalid TOML frontmatter at line 3, column 1 in `authors`: "),
+            "{err}"
+        );
+
+        let err = Frontmatter::read("{\n\"title\": \"Test\",\n  \"authors\": 1\n}").unwrap_err();
+        assert!(
+            err.to_string().starts_with("Invalid JSON frontmatter at line 3, column 3 in `authors`: "),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_frontmatter_error_key() {
+        let markdown = "---\ntitle: Test\ncreated_at: yesterday\nauthors: [Alice]\n---";
+        let err = Frontmatter::read(markdown).unwrap_err().with_path(Some(Path::new("content/index.md")));
+        assert_eq!(err.key.as_deref(), Some("created_at"));
+        assert_eq!(
+            err.to_string(),
+            "Invalid YAML frontmatter in content/index.md at line 3, column 1 in `created_at`: Unrecognized datetime format: yesterday"
+        );
+    }
+
+    #[test]
+    fn test_read_lenient() {
+        let (frontmatter, errors) = Frontmatter::read_lenient("---\ntitle: Test\ncreated_at: yesterday\ntoc_depth: many\n---");
+        let frontmatter = frontmatter.unwrap();
+        assert_eq!(frontmatter.title, Some("Test".to_string()));
+        assert_eq!(frontmatter.created_at, None);
+        let keys = errors.iter().map(|err| err.key.as_deref().unwrap()).collect::<Vec<_>>();
+        assert_eq!(keys, ["created_at", "toc_depth"]);
+
+        let (frontmatter, errors) = Frontmatter::read_lenient("---\ntitle: [\n---");
+        assert_eq!(frontmatter.unwrap().title, None);
+        assert_eq!(errors.len(), 1);
+    }
+
     #[derive(Debug, Deserialize, Default)]
     #[serde(default)]
     struct TestWrapper {
@@ -0,0 +1,125 @@
+use super::frontmatter::Frontmatter;
+use crate::{Error, Result};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+const COMMIT_MARKER: char = '\x1e';
+const FIELD_SEPARATOR: char = '\x1f';
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct GitInfo {
+    pub created_at: Option<PrimitiveDateTime>,
+    pub updated_at: Option<PrimitiveDateTime>,
+    /// Unique commit authors in order of their first contribution
+    pub authors: Vec<String>,
+}
+
+impl GitInfo {
+    /// Fills the dates and authors missing in `frontmatter`.
+    pub fn apply(&self, frontmatter: &mut Frontmatter) {
+        frontmatter.created_at = frontmatter.created_at.or(self.created_at);
+        frontmatter.updated_at = frontmatter.updated_at.or(self.updated_at);
+        if frontmatter.authors.is_empty() {
+            frontmatter.authors = self.authors.clone();
+        }
+    }
+}
+
+/// Reads the history of all files below `dir` with a single `git log`, keyed by `dir` joined
+/// with the path of the file relative to `dir`.
+pub fn read_history(dir: &Path) -> Result<HashMap<PathBuf, GitInfo>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "core.quotePath=false", "log", "--name-only", "--relative", "--no-renames"])
+        .arg(format!("--format={COMMIT_MARKER}%at{FIELD_SEPARATOR}%an"))
+        .output()
+        .map_err(Error::io(dir))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::io(dir)(io::Error::other(format!("git log failed: {}", stderr.trim()))));
+    }
+
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout))
+        .map_err(Error::io(dir))?
+        .into_iter()
+        .map(|(path, info)| (dir.join(path), info))
+        .collect())
+}
+
+/// Parses the log, which lists the newest commit first.
+fn parse_log(log: &str) -> io::Result<HashMap<String, GitInfo>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut history = HashMap::<String, GitInfo>::new();
+    for commit in log.split(COMMIT_MARKER).filter(|commit| !commit.trim().is_empty()) {
+        let mut lines = commit.lines();
+        let header = lines.next().unwrap_or_default();
+        let Some((timestamp, author)) = header.split_once(FIELD_SEPARATOR) else {
+            return Err(invalid(format!("Unexpected git log entry `{header}`")));
+        };
+        let time = timestamp
+            .parse()
+            .ok()
+            .and_then(|timestamp| OffsetDateTime::from_unix_timestamp(timestamp).ok())
+            .ok_or_else(|| invalid(format!("Invalid git commit time `{timestamp}`")))?;
+        let time = PrimitiveDateTime::new(time.date(), time.time());
+
+        for file in lines.filter(|line| !line.is_empty()) {
+            let info = history.entry(file.to_string()).or_default();
+            info.updated_at.get_or_insert(time);
+            info.created_at = Some(time);
+            // older commits come later, move the author to the front
+            info.authors.retain(|known| known != author);
+            info.authors.insert(0, author.to_string());
+        }
+    }
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_parse_log() {
+        let log = "\x1e1700000000\x1fBob\n\ncontent/index.md\ncontent/docs/page.md\n\
+                   \x1e1600000000\x1fAlice\n\ncontent/index.md\n\
+                   \x1e1500000000\x1fBob\n\ncontent/index.md\n";
+        let history = parse_log(log).unwrap();
+
+        let index = &history["content/index.md"];
+        assert_eq!(index.created_at, Some(datetime!(2017-07-14 02:40:00)));
+        assert_eq!(index.updated_at, Some(datetime!(2023-11-14 22:13:20)));
+        assert_eq!(index.authors, ["Bob", "Alice"]);
+
+        let page = &history["content/docs/page.md"];
+        assert_eq!(page.created_at, page.updated_at);
+        assert_eq!(page.authors, ["Bob"]);
+
+        let err = parse_log("\x1eyesterday\x1fBob\n\ncontent/index.md\n").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid git commit time `yesterday`");
+    }
+
+    #[test]
+    fn test_apply_keeps_frontmatter() {
+        let info = GitInfo {
+            created_at: Some(datetime!(2020-01-01 0:00)),
+            updated_at: Some(datetime!(2021-01-01 0:00)),
+            authors: vec!["Alice".to_string()],
+        };
+        let mut frontmatter = Frontmatter {
+            created_at: Some(datetime!(2019-01-01 0:00)),
+            ..Default::default()
+        };
+        info.apply(&mut frontmatter);
+        assert_eq!(frontmatter.created_at, Some(datetime!(2019-01-01 0:00)));
+        assert_eq!(frontmatter.updated_at, info.updated_at);
+        assert_eq!(frontmatter.authors, ["Alice"]);
+    }
+}
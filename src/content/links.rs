@@ -0,0 +1,125 @@
+use super::{
+    markdown::{MarkdownError, RenderContext},
+    page::content_url,
+};
+use comrak::nodes::{AstNode, NodeValue};
+use std::path::{Component, Path, PathBuf};
+
+/// Rewrites link and image destinations pointing to markdown files (`other.md`,
+/// `../dir/index.md`, `@/docs/page.md#anchor`) to the URL of the rendered page. Absolute
+/// paths like `/docs/page.md` are relative to the content directory, just like `@/`.
+/// Links are kept as is if the source file or the content directory are unknown.
+pub(crate) fn resolve_links<'a>(root: &'a AstNode<'a>, ctx: &RenderContext) -> Result<(), MarkdownError> {
+    let (Some(source), Some(content_dir)) = (&ctx.path, &ctx.content_dir) else {
+        return Ok(());
+    };
+
+    for node in root.descendants() {
+        let mut data = node.data.borrow_mut();
+        let line = data.sourcepos.start.line;
+        if let NodeValue::Link(link) | NodeValue::Image(link) = &mut data.value
+            && let Some(url) =
+                resolve_link(&link.url, source, content_dir, &ctx.url_prefix).map_err(|message| MarkdownError::Link { line, message })?
+        {
+            link.url = url;
+        }
+    }
+    Ok(())
+}
+
+fn resolve_link(url: &str, source: &Path, content_dir: &Path, prefix: &str) -> Result<Option<String>, String> {
+    let split = url.find(['#', '?']).unwrap_or(url.len());
+    let (path, suffix) = url.split_at(split);
+    if !path.ends_with(".md") || path.contains(':') || path.starts_with("//") {
+        return Ok(None);
+    }
+
+    let target = match path.strip_prefix("@/").or_else(|| path.strip_prefix('/')) {
+        Some(path) => content_dir.join(path),
+        None => source.parent().unwrap_or(content_dir).join(path),
+    };
+    let target = normalize(&target);
+    if !target.is_file() {
+        return Err(format!("Broken link `{url}`, {} does not exist", target.display()));
+    }
+
+    let Ok(relative) = target.strip_prefix(normalize(content_dir)) else {
+        return Err(format!("Link `{url}` points outside of {}", content_dir.display()));
+    };
+    Ok(Some(format!("{prefix}{}{suffix}", content_url(relative))))
+}
+
+/// Lexically resolves `.` and `..` components without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn content_dir() -> tempfile::TempDir {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        for file in ["index.md", "about.md", "docs/index.md", "docs/page.md"] {
+            fs::write(dir.join(file), "# Test").unwrap();
+        }
+        temp
+    }
+
+    #[test]
+    fn test_resolve_link() {
+        let temp = content_dir();
+        let dir = temp.path();
+        let source = dir.join("docs/page.md");
+        let resolve = |url| resolve_link(url, &source, dir, "").unwrap();
+
+        assert_eq!(resolve("index.md"), Some("/docs/".to_string()));
+        assert_eq!(resolve("../about.md#team"), Some("/about#team".to_string()));
+        assert_eq!(resolve("./../index.md"), Some("/".to_string()));
+        assert_eq!(resolve("@/docs/page.md?q=1"), Some("/docs/page?q=1".to_string()));
+        assert_eq!(resolve("/docs/index.md#intro"), Some("/docs/#intro".to_string()));
+        assert_eq!(resolve("https://example.com/README.md"), None);
+        assert_eq!(resolve("image.png"), None);
+        assert_eq!(resolve("#anchor"), None);
+        assert_eq!(
+            resolve_link("@/about.md", &source, dir, "/root").unwrap(),
+            Some("/root/about".to_string())
+        );
+    }
+
+    #[test]
+    fn test_broken_link() {
+        let temp = content_dir();
+        let dir = temp.path();
+        let err = resolve_link("missing.md", &dir.join("index.md"), dir, "").unwrap_err();
+        assert!(err.to_string().starts_with("Broken link `missing.md`"));
+        let err = resolve_link("/docs/missing.md", &dir.join("index.md"), dir, "").unwrap_err();
+        assert!(err.to_string().starts_with("Broken link `/docs/missing.md`"));
+    }
+
+    #[test]
+    fn test_text_is_not_rewritten() {
+        let temp = content_dir();
+        let dir = temp.path();
+        let ctx = RenderContext {
+            path: Some(dir.join("index.md")),
+            content_dir: Some(dir.to_path_buf()),
+            ..Default::default()
+        };
+        let page = crate::content::page::Page::render("See README.md and [about](about.md)", &ctx).unwrap();
+        assert_eq!(page.content, "<p>See README.md and <a href=\"/about\">about</a></p>\n");
+    }
+}
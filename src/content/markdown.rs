@@ -1,84 +1,513 @@
-use super::{frontmatter::Frontmatter, page::PageHeading};
+use super::{
+    admonitions::{preprocess_containers, render_admonitions},
+    diagrams::{DEFAULT_MERMAID_URL, mermaid_script, render_diagrams},
+    frontmatter::{Frontmatter, RawFrontmatter},
+    links::resolve_links,
+    math::render_math,
+    page::{PageHeading, PageStats, Summary},
+    shortcodes::strip_placeholders,
+};
+use crate::{Error, Result, config::MarkdownConfig};
 use comrak::{
-    Anchorizer, Arena, ComrakPlugins, Options, format_html_with_plugins, html,
-    nodes::{AstNode, NodeValue},
+    Anchorizer, Arena, ComrakPlugins, Options,
+    arena_tree::Node,
+    format_html_with_plugins, html,
+    nodes::{Ast, AstNode, NodeHtmlBlock, NodeValue},
     parse_document, plugins,
 };
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashSet,
+    fmt, io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use tera::Tera;
+use tracing::warn;
 
-pub fn render_html(content: &str, headings: &mut Vec<PageHeading>, frontmatter: &mut Frontmatter) -> anyhow::Result<String> {
-    //TODO: Global Options based on config
-    let mut options = Options::default();
-    options.extension.front_matter_delimiter = Some("---".to_string());
-    options.extension.header_ids = Some("".to_string());
+#[derive(Default, Clone)]
+pub struct RenderContext<'a> {
+    /// Templates used to resolve shortcodes and admonitions, shortcodes are left as is if `None`
+    pub tera: Option<&'a Tera>,
+    /// Site data, available as `data` to shortcodes and admonitions
+    pub data: Option<&'a serde_json::Value>,
+    pub config: MarkdownConfig,
+    /// Directory for rendered diagrams, which are only cached in memory if `None`
+    pub cache_dir: Option<PathBuf>,
+    /// Markdown file being rendered, relative links are resolved against it
+    pub path: Option<PathBuf>,
+    pub content_dir: Option<PathBuf>,
+    /// Prepended to resolved links, the project's `rootDir` in build mode
+    pub url_prefix: String,
+}
 
-    let arena = Arena::new();
-    let root = parse_document(&arena, content, &options);
+/// Why markdown could not be rendered, the source of [`Error::Markdown`].
+#[derive(Debug)]
+pub enum MarkdownError {
+    /// A link to a markdown file is broken or points outside of the content directory
+    Link {
+        line: usize,
+        message: String,
+    },
+    /// A math expression is not supported by the MathML converter
+    Math {
+        line: usize,
+        message: String,
+    },
+    /// A dot diagram could not be parsed or laid out
+    Diagram {
+        line: usize,
+        message: String,
+    },
+    /// A shortcode invocation is malformed, e.g. unclosed or with an invalid argument
+    Shortcode(String),
+    /// There is no `shortcodes/<name>.html` template for the shortcode
+    UnknownShortcode {
+        name: String,
+        location: String,
+    },
+    /// The template of a shortcode or an admonition failed to render
+    Template {
+        message: String,
+        source: tera::Error,
+    },
+    Html(io::Error),
+}
 
-    //sanitze the content
-    for node in root.descendants() {
-        if let NodeValue::Text(ref mut text) = node.data.borrow_mut().value {
-            *text = text.replace(".md", "/");
+impl fmt::Display for MarkdownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkdownError::Link { line, message } => write!(f, "Invalid link at line {line}: {message}"),
+            MarkdownError::Math { line, message } => write!(f, "Invalid math expression at line {line}: {message}"),
+            MarkdownError::Diagram { line, message } => write!(f, "Invalid dot diagram at line {line}: {message}"),
+            MarkdownError::Shortcode(message) | MarkdownError::Template { message, .. } => message.fmt(f),
+            MarkdownError::UnknownShortcode { name, location } => {
+                write!(f, "Unknown shortcode `{name}` at {location}, expected template shortcodes/{name}.html")
+            }
+            MarkdownError::Html(_) => write!(f, "Failed to write HTML"),
         }
-        if let NodeValue::FrontMatter(ref mut fm) = node.data.borrow_mut().value {
-            *fm = String::new();
+    }
+}
+
+impl std::error::Error for MarkdownError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MarkdownError::Template { source, .. } => Some(source),
+            MarkdownError::Html(source) => Some(source),
+            _ => None,
         }
     }
+}
+
+impl From<io::Error> for MarkdownError {
+    fn from(err: io::Error) -> Self {
+        MarkdownError::Html(err)
+    }
+}
+
+const DEFAULT_SUMMARY_WORDS: usize = 50;
+const DEFAULT_WORDS_PER_MINUTE: usize = 200;
+const MORE_MARKER: &str = "<!-- more -->";
+
+pub fn render_html(
+    content: &str,
+    ctx: &RenderContext,
+    headings: &mut Vec<PageHeading>,
+    summary: &mut Option<Summary>,
+    stats: &mut PageStats,
+    frontmatter: &mut Frontmatter,
+) -> Result<String> {
+    render_document(content, ctx, headings, summary, stats, frontmatter).map_err(|source| Error::Markdown {
+        path: ctx.path.clone(),
+        source,
+    })
+}
+
+fn render_document(
+    content: &str,
+    ctx: &RenderContext,
+    headings: &mut Vec<PageHeading>,
+    summary: &mut Option<Summary>,
+    stats: &mut PageStats,
+    frontmatter: &mut Frontmatter,
+) -> Result<String, MarkdownError> {
+    let options = options();
+    let arena = Arena::new();
+    let root = parse_document(&arena, &preprocess_containers(&blank_frontmatter(content)), &options);
+    if !ctx.config.raw_html {
+        omit_unsafe_html(root);
+    }
 
-    extract_headings(root, headings);
+    resolve_links(root, ctx)?;
+    extract_headings(&arena, root, ctx.config.heading_anchor.as_deref(), headings);
 
     // get title
     if frontmatter.title.is_none() {
-        frontmatter.title = Some(get_document_title(content)?);
+        frontmatter.title = Some(get_document_title(root));
     }
 
+    *stats = count_words(root, &ctx.config);
+    render_math_nodes(root, &ctx.config, ctx.path.as_deref())?;
+    let has_mermaid = render_diagrams(root, ctx.cache_dir.as_deref())?;
+
     let syntect_plugin = plugins::syntect::SyntectAdapter::new(Some("InspiredGitHub"));
     let mut plugins = ComrakPlugins::default();
     plugins.render.codefence_syntax_highlighter = Some(&syntect_plugin);
 
+    render_admonitions(root, &options, &plugins, ctx.tera, ctx.data)?;
+    *summary = extract_summary(root, &options, &plugins, ctx.config.summary_words.unwrap_or(DEFAULT_SUMMARY_WORDS))?;
+
     let mut html = vec![];
     format_html_with_plugins(root, &options, &mut html, &plugins)?;
+    if has_mermaid {
+        let url = match ctx.config.mermaid_url.as_deref().unwrap_or(DEFAULT_MERMAID_URL) {
+            url if url.starts_with('/') => format!("{}{url}", ctx.url_prefix),
+            url => url.to_string(),
+        };
+        html.extend_from_slice(mermaid_script(&url).as_bytes());
+    }
+
+    // comrak only writes UTF-8
+    Ok(String::from_utf8_lossy(&html).into_owned())
+}
+
+/// Renders a markdown snippet like a page, e.g. for the `markdown` filter. Links and headings
+/// are left as is.
+pub fn render_snippet(content: &str, config: &MarkdownConfig) -> Result<String> {
+    let render = || -> Result<String, MarkdownError> {
+        let options = options();
+        let arena = Arena::new();
+        let root = parse_document(&arena, content, &options);
+        if !config.raw_html {
+            omit_unsafe_html(root);
+        }
+        render_math_nodes(root, config, None)?;
+
+        let syntect_plugin = plugins::syntect::SyntectAdapter::new(Some("InspiredGitHub"));
+        let mut plugins = ComrakPlugins::default();
+        plugins.render.codefence_syntax_highlighter = Some(&syntect_plugin);
 
-    String::from_utf8(html).map_err(|e| anyhow::anyhow!("Failed to convert HTML to UTF-8: {}", e))
+        let mut html = vec![];
+        format_html_with_plugins(root, &options, &mut html, &plugins)?;
+        Ok(String::from_utf8_lossy(&html).into_owned())
+    };
+    render().map_err(|source| Error::Markdown { path: None, source })
 }
 
-fn extract_headings<'a>(root: &'a AstNode<'a>, headings: &mut Vec<PageHeading>) {
+fn options() -> Options<'static> {
+    //TODO: Global Options based on config
+    let mut options = Options::default();
+    options.extension.alerts = true;
+    options.extension.math_dollars = true;
+    options.extension.math_code = true;
+    // html nodes created for anchors, math, diagrams and admonitions need to be emitted as is,
+    // raw html and dangerous urls of the content are removed by `omit_unsafe_html` unless enabled
+    options.render.unsafe_ = true;
+    options
+}
+
+/// Does what comrak does without `unsafe_` for the content only: raw html is replaced with a
+/// comment and `javascript:`, `data:` and similar link and image urls are emptied. Html comments
+/// are kept for markers like `<!-- more -->`.
+fn omit_unsafe_html<'a>(root: &'a AstNode<'a>) {
+    let is_comment = |html: &str| {
+        let html = html.trim();
+        html.starts_with("<!--") && html.find("-->") == Some(html.len() - 3)
+    };
     for node in root.descendants() {
-        if let NodeValue::Heading(ref heading) = node.data.borrow().value {
-            let mut text_content = Vec::with_capacity(30);
-            html::collect_text(node, &mut text_content);
-
-            let text = String::from_utf8(text_content).unwrap();
-            let mut anchorizer = Anchorizer::new();
-            let id = anchorizer.anchorize(text.clone());
-
-            headings.push(PageHeading {
-                level: heading.level,
-                id,
-                title: text,
-                children: vec![]
-            });
+        let mut data = node.data.borrow_mut();
+        match &mut data.value {
+            NodeValue::HtmlBlock(html) if !is_comment(&html.literal) => html.literal = "<!-- raw HTML omitted -->\n".to_string(),
+            NodeValue::HtmlInline(html) if !is_comment(html) => *html = "<!-- raw HTML omitted -->".to_string(),
+            NodeValue::Link(link) | NodeValue::Image(link) if html::dangerous_url(link.url.as_bytes()) => link.url.clear(),
+            _ => {}
         }
     }
 }
 
-fn get_document_title(document: &str) -> anyhow::Result<String> {
-    let arena = Arena::new();
-    let root = parse_document(&arena, document, &Options::default());
+/// Replaces the frontmatter with empty lines, which keeps the line numbers of the content intact.
+fn blank_frontmatter(content: &str) -> Cow<'_, str> {
+    match RawFrontmatter::find(content) {
+        Some(raw) => Cow::Owned("\n".repeat(content[..raw.end].matches('\n').count()) + &content[raw.end..]),
+        None => Cow::Borrowed(content),
+    }
+}
+
+/// Renders math nodes, unsupported commands only cause a warning naming `path` and the line.
+fn render_math_nodes<'a>(root: &'a AstNode<'a>, config: &MarkdownConfig, path: Option<&Path>) -> Result<(), MarkdownError> {
+    for node in root.descendants() {
+        let mut data = node.data.borrow_mut();
+        let line = data.sourcepos.start.line;
+        let (tex, display) = match &data.value {
+            NodeValue::Math(math) => (&math.literal, math.display_math),
+            NodeValue::CodeBlock(code) if code.info == "math" => (&code.literal, true),
+            _ => continue,
+        };
+        let math = render_math(tex, display, config.math).map_err(|message| MarkdownError::Math { line, message })?;
+        for command in &math.unknown {
+            let location = path
+                .map(|path| format!("{}:{line}", path.display()))
+                .unwrap_or_else(|| format!("line {line}"));
+            warn!("Unsupported math command {command} at {location}, rendered as an error");
+        }
+        data.value = if matches!(data.value, NodeValue::Math(_)) {
+            NodeValue::HtmlInline(math.html)
+        } else {
+            NodeValue::HtmlBlock(NodeHtmlBlock {
+                block_type: 6,
+                literal: format!("{}\n", math.html),
+            })
+        };
+    }
+    Ok(())
+}
+
+/// Assigns ids to all headings using a single anchorizer, so they match the outline, and inserts
+/// the anchor elements. An explicit `{#custom-id}` at the end of a heading takes precedence.
+fn extract_headings<'a>(arena: &'a Arena<AstNode<'a>>, root: &'a AstNode<'a>, anchor_symbol: Option<&str>, headings: &mut Vec<PageHeading>) {
+    let nodes = root
+        .descendants()
+        .filter_map(|node| {
+            let data = node.data.borrow();
+            let NodeValue::Heading(ref heading) = data.value else {
+                return None;
+            };
+            Some((node, heading.level, data.sourcepos.start))
+        })
+        .collect::<Vec<_>>();
+    let custom_ids = nodes.iter().map(|(node, ..)| take_custom_id(node)).collect::<Vec<_>>();
+    let reserved = custom_ids.iter().flatten().cloned().collect::<HashSet<_>>();
+
+    let mut anchorizer = Anchorizer::new();
+    for ((node, level, start), custom_id) in nodes.into_iter().zip(custom_ids) {
+        let mut text_content = Vec::with_capacity(30);
+        html::collect_text(node, &mut text_content);
+        let text = strip_placeholders(&String::from_utf8_lossy(&text_content));
+
+        let id = custom_id.unwrap_or_else(|| {
+            // explicit ids are not known to the anchorizer, skip until there is no collision
+            loop {
+                let id = anchorizer.anchorize(text.clone());
+                if !reserved.contains(&id) {
+                    break id;
+                }
+            }
+        });
 
+        let html_node = |html: String| arena.alloc(Node::new(RefCell::new(Ast::new(NodeValue::HtmlInline(html), start))));
+        node.prepend(html_node(format!(
+            "<a href=\"#{id}\" aria-hidden=\"true\" class=\"anchor\" id=\"{id}\"></a>"
+        )));
+        if let Some(symbol) = anchor_symbol {
+            node.append(html_node(format!(
+                " <a href=\"#{id}\" class=\"heading-anchor\" aria-label=\"Link to this section\">{symbol}</a>"
+            )));
+        }
+
+        headings.push(PageHeading {
+            level,
+            id,
+            title: text,
+            children: vec![],
+        });
+    }
+}
+
+/// Removes a trailing `{#custom-id}` from the heading text and returns the id. Ids with other
+/// characters than ASCII letters, digits, `-`, `_`, `:` and `.` are kept as heading text since
+/// the id is written into attributes as is.
+fn take_custom_id<'a>(heading: &'a AstNode<'a>) -> Option<String> {
+    let last = heading.last_child()?;
+    let mut data = last.data.borrow_mut();
+    let NodeValue::Text(ref mut text) = data.value else {
+        return None;
+    };
+
+    let trimmed = text.trim_end();
+    let start = trimmed.rfind("{#")?;
+    let id = trimmed[start + 2..].strip_suffix('}')?;
+    let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.');
+    if id.is_empty() || !id.chars().all(valid) {
+        return None;
+    }
+
+    let id = id.to_string();
+    text.truncate(start);
+    text.truncate(text.trim_end().len());
+    Some(id)
+}
+
+/// Summary from the top level blocks before `<!-- more -->` without headings, or the first
+/// paragraph otherwise. The plain text version is limited to `words` words.
+fn extract_summary<'a>(root: &'a AstNode<'a>, options: &Options, plugins: &ComrakPlugins, words: usize) -> Result<Option<Summary>, MarkdownError> {
+    let is_marker = |node: &'a AstNode<'a>| matches!(&node.data.borrow().value, NodeValue::HtmlBlock(html) if html.literal.trim() == MORE_MARKER);
+    let nodes = if root.children().any(is_marker) {
+        root.children()
+            .take_while(|node| !is_marker(node))
+            .filter(|node| !matches!(node.data.borrow().value, NodeValue::Heading(_)))
+            .collect::<Vec<_>>()
+    } else {
+        root.children()
+            .find(|node| matches!(node.data.borrow().value, NodeValue::Paragraph))
+            .into_iter()
+            .collect()
+    };
+    if nodes.is_empty() {
+        return Ok(None);
+    }
+
+    let mut html = vec![];
+    let mut text = vec![];
+    for node in nodes {
+        format_html_with_plugins(node, options, &mut html, plugins)?;
+        html::collect_text(node, &mut text);
+        text.push(b' ');
+    }
+
+    let text = String::from_utf8_lossy(&text);
+    let mut text_words = text.split_whitespace();
+    let mut text = text_words.by_ref().take(words).collect::<Vec<_>>().join(" ");
+    if text_words.next().is_some() {
+        text.push('…');
+    }
+
+    Ok(Some(Summary {
+        html: String::from_utf8_lossy(&html).into_owned(),
+        text,
+    }))
+}
+
+/// Counts prose words, code blocks only contribute to the reading time with `code_weight`.
+fn count_words<'a>(root: &'a AstNode<'a>, config: &MarkdownConfig) -> PageStats {
+    let mut words = 0;
+    let mut code_words = 0;
+    for node in root.descendants() {
+        match &node.data.borrow().value {
+            NodeValue::Text(text) => words += strip_placeholders(text).split_whitespace().count(),
+            NodeValue::Code(code) => words += code.literal.split_whitespace().count(),
+            NodeValue::CodeBlock(code) => code_words += code.literal.split_whitespace().count(),
+            _ => {}
+        }
+    }
+
+    let weighted = words as f64 + code_words as f64 * config.code_weight.unwrap_or_default();
+    PageStats {
+        word_count: words,
+        reading_time: reading_time(weighted, config),
+    }
+}
+
+/// Reading time in minutes, rounded up.
+pub(crate) fn reading_time(words: f64, config: &MarkdownConfig) -> usize {
+    let words_per_minute = config.words_per_minute.unwrap_or(DEFAULT_WORDS_PER_MINUTE).max(1);
+    (words / words_per_minute as f64).ceil() as usize
+}
+
+fn get_document_title<'a>(root: &'a AstNode<'a>) -> String {
     for node in root.children() {
-        let header = match node.data.clone().into_inner().value {
-            NodeValue::Heading(c) => c,
+        let level = match node.data.borrow().value {
+            NodeValue::Heading(ref heading) => heading.level,
             _ => continue,
         };
 
-        if header.level != 1 {
+        if level != 1 {
             continue;
         }
 
         let mut text = Vec::with_capacity(30);
         html::collect_text(node, &mut text);
-        return Ok(String::from_utf8(text)?);
+        return strip_placeholders(&String::from_utf8_lossy(&text));
+    }
+
+    "Untitled Document".to_string()
+}
+
+/// Byte ranges of frontmatter, fenced code blocks and code spans, which must not be scanned for
+/// shortcodes.
+pub(crate) fn verbatim_ranges(content: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let lines = content.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line))
+    });
+
+    let mut open: Option<(usize, char, usize)> = None;
+    let frontmatter_end = RawFrontmatter::find(content).map(|raw| raw.end).unwrap_or_default();
+    if frontmatter_end > 0 {
+        ranges.push(0..frontmatter_end);
+    }
+
+    for (start, line) in lines.skip_while(|(start, _)| *start < frontmatter_end) {
+        let trimmed = line.trim_end();
+        match open {
+            Some((open_start, fence, count)) => {
+                let indented = trimmed.trim_start();
+                if indented.len() >= count && indented.chars().all(|c| c == fence) {
+                    ranges.push(open_start..start + line.len());
+                    open = None;
+                }
+            }
+            None => {
+                let indented = line.trim_start_matches(' ');
+                if line.len() - indented.len() > 3 {
+                    continue;
+                }
+                if let Some(fence) = indented.chars().next().filter(|c| *c == '`' || *c == '~') {
+                    let count = indented.chars().take_while(|c| *c == fence).count();
+                    if count >= 3 {
+                        open = Some((start, fence, count));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some((open_start, _, _)) = open {
+        ranges.push(open_start..content.len());
     }
+    let spans = code_spans(content, &ranges);
+    ranges.extend(spans);
+    ranges
+}
+
+/// Inline code spans outside of `blocks`: a backtick run up to the next run of the same length
+/// within the same paragraph.
+fn code_spans(content: &str, blocks: &[Range<usize>]) -> Vec<Range<usize>> {
+    let run_len = |at: usize| content[at..].bytes().take_while(|b| *b == b'`').count();
+    let mut spans = vec![];
+    let mut pos = 0;
+    while let Some(found) = content[pos..].find('`') {
+        let start = pos + found;
+        if let Some(block) = blocks.iter().find(|block| block.contains(&start)) {
+            pos = block.end;
+            continue;
+        }
+        let len = run_len(start);
+        pos = start + len;
+        if content[..start].ends_with('\\') {
+            continue;
+        }
 
-    Ok("Untitled Document".to_string())
+        let mut search = pos;
+        let close = loop {
+            let Some(found) = content[search..].find('`') else {
+                break None;
+            };
+            let close = search + found;
+            match run_len(close) {
+                close_len if close_len == len => break Some(close + len),
+                close_len => search = close + close_len,
+            }
+        };
+        if let Some(end) = close
+            && !content[pos..end].contains("\n\n")
+            && !blocks.iter().any(|block| (start..end).contains(&block.start))
+        {
+            spans.push(start..end);
+            pos = end;
+        }
+    }
+    spans
 }
@@ -0,0 +1,773 @@
+use super::escape;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{iter::Peekable, str::Chars};
+
+/// Returns the formatted message as the error of the expression.
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err(format!($($arg)*))
+    };
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MathRenderer {
+    /// Converts LaTeX to MathML at build time
+    #[default]
+    MathML,
+    /// Emits `\(...\)` and `\[...\]` spans to be picked up by KaTeX on the client
+    KaTeX,
+}
+
+/// Rendered math expression, `unknown` lists the commands that were rendered as `<merror>`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct RenderedMath {
+    pub html: String,
+    pub unknown: Vec<String>,
+}
+
+pub(crate) fn render_math(tex: &str, display: bool, renderer: MathRenderer) -> Result<RenderedMath, String> {
+    match renderer {
+        MathRenderer::MathML => latex_to_mathml(tex, display),
+        MathRenderer::KaTeX => {
+            let tex = escape(tex.trim());
+            let html = if display {
+                format!("<span class=\"math display\">\\[{tex}\\]</span>")
+            } else {
+                format!("<span class=\"math inline\">\\({tex}\\)</span>")
+            };
+            Ok(RenderedMath { html, unknown: vec![] })
+        }
+    }
+}
+
+/// Converts a subset of LaTeX math (the commonly used commands, fractions, roots, scripts,
+/// `\left`/`\right` and matrix-like environments) to presentation MathML. Unknown commands are
+/// rendered as `<merror>` and reported in [`RenderedMath::unknown`], malformed expressions are an
+/// error; pages needing more set `math: katex`. The converter is kept small and in-tree since the
+/// KaTeX bindings would embed a JavaScript engine in the build.
+pub(crate) fn latex_to_mathml(tex: &str, display: bool) -> Result<RenderedMath, String> {
+    let mut parser = MathParser {
+        chars: tex.chars().peekable(),
+        display,
+        unknown: vec![],
+    };
+    let body = parser.parse_row(Until::End)?;
+    let display = if display { " display=\"block\"" } else { "" };
+    let html = format!(
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"{display}><semantics>{body}<annotation encoding=\"application/x-tex\">{}</annotation></semantics></math>",
+        escape(tex.trim())
+    );
+    Ok(RenderedMath {
+        html,
+        unknown: parser.unknown,
+    })
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Until {
+    End,
+    Brace,
+    Bracket,
+    Right,
+    Environment,
+}
+
+enum Token {
+    Command(String),
+    Char(char),
+}
+
+struct MathParser<'a> {
+    chars: Peekable<Chars<'a>>,
+    display: bool,
+    unknown: Vec<String>,
+}
+
+impl MathParser<'_> {
+    fn parse_row(&mut self, until: Until) -> Result<String, String> {
+        let nodes = self.parse_nodes(until)?;
+        Ok(mrow(nodes))
+    }
+
+    fn parse_nodes(&mut self, until: Until) -> Result<Vec<String>, String> {
+        let mut nodes = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek() {
+                None if until == Until::End => return Ok(nodes),
+                None => bail!("Unexpected end of expression, missing {}", until.closing()),
+                Some('}') if until == Until::Brace => {
+                    self.chars.next();
+                    return Ok(nodes);
+                }
+                Some(']') if until == Until::Bracket => {
+                    self.chars.next();
+                    return Ok(nodes);
+                }
+                Some('}') => bail!("Unexpected `}}`"),
+                Some('&') if until == Until::Environment => return Ok(nodes),
+                _ => {}
+            }
+
+            if until == Until::Right && self.eat_command("right") {
+                return Ok(nodes);
+            }
+            if until == Until::Environment && (self.peek_command("end") || self.peek_command("\\")) {
+                return Ok(nodes);
+            }
+
+            let atom = self.parse_atom()?;
+            let atom = self.parse_scripts(atom)?;
+            if !atom.is_empty() {
+                nodes.push(atom);
+            }
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<String, String> {
+        let token = self.next_token().ok_or("Unexpected end of expression")?;
+        match token {
+            Token::Char('{') => self.parse_row(Until::Brace),
+            Token::Char(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                while let Some(&c) = self.chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    number.push(c);
+                    self.chars.next();
+                }
+                Ok(format!("<mn>{number}</mn>"))
+            }
+            Token::Char(c) if c.is_alphabetic() => Ok(format!("<mi>{}</mi>", escape(&c.to_string()))),
+            Token::Char(c @ ('^' | '_')) => bail!("Unexpected `{c}`, script without base"),
+            Token::Char(c) => Ok(format!("<mo>{}</mo>", escape(&c.to_string()))),
+            Token::Command(command) => self.parse_command(&command),
+        }
+    }
+
+    fn parse_command(&mut self, command: &str) -> Result<String, String> {
+        if let Some(symbol) = identifier(command) {
+            return Ok(format!("<mi>{symbol}</mi>"));
+        }
+        if let Some(symbol) = operator(command) {
+            return Ok(format!("<mo>{symbol}</mo>"));
+        }
+        if let Some(symbol) = large_operator(command) {
+            return Ok(format!("<mo movablelimits=\"true\">{symbol}</mo>"));
+        }
+        if FUNCTIONS.contains(&command) {
+            return Ok(format!("<mi>{command}</mi><mo>&#x2061;</mo>"));
+        }
+        if let Some(width) = space(command) {
+            return Ok(format!("<mspace width=\"{width}\"/>"));
+        }
+        if let Some(accent) = accent(command) {
+            let base = self.parse_argument(command)?;
+            return Ok(format!("<mover accent=\"true\">{base}<mo>{accent}</mo></mover>"));
+        }
+        if let Some(variant) = variant(command) {
+            let base = self.parse_argument(command)?;
+            return Ok(format!("<mstyle mathvariant=\"{variant}\">{base}</mstyle>"));
+        }
+
+        match command {
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = self.parse_argument(command)?;
+                let denominator = self.parse_argument(command)?;
+                Ok(format!("<mfrac>{numerator}{denominator}</mfrac>"))
+            }
+            "binom" => {
+                let top = self.parse_argument(command)?;
+                let bottom = self.parse_argument(command)?;
+                Ok(format!(
+                    "<mrow><mo>(</mo><mfrac linethickness=\"0\">{top}{bottom}</mfrac><mo>)</mo></mrow>"
+                ))
+            }
+            "overset" | "underset" => {
+                let script = self.parse_argument(command)?;
+                let base = self.parse_argument(command)?;
+                let tag = if command == "overset" { "mover" } else { "munder" };
+                Ok(format!("<{tag}>{base}{script}</{tag}>"))
+            }
+            // style switches only change the size, which MathML derives from the context
+            "displaystyle" | "textstyle" => Ok(String::new()),
+            "sqrt" => {
+                self.skip_whitespace();
+                if self.chars.peek() == Some(&'[') {
+                    self.chars.next();
+                    let index = self.parse_row(Until::Bracket)?;
+                    let base = self.parse_argument(command)?;
+                    Ok(format!("<mroot>{base}{index}</mroot>"))
+                } else {
+                    let base = self.parse_argument(command)?;
+                    Ok(format!("<msqrt>{base}</msqrt>"))
+                }
+            }
+            "text" | "textrm" | "mbox" | "operatorname" => {
+                let text = self.parse_raw_group(command)?;
+                if command == "operatorname" {
+                    Ok(format!("<mi>{}</mi><mo>&#x2061;</mo>", escape(&text)))
+                } else {
+                    Ok(format!("<mtext>{}</mtext>", escape(&text)))
+                }
+            }
+            "left" => {
+                let open = self.parse_delimiter()?;
+                let inner = self.parse_nodes(Until::Right)?;
+                let close = self.parse_delimiter()?;
+                Ok(format!(
+                    "<mrow><mo fence=\"true\">{open}</mo>{}<mo fence=\"true\">{close}</mo></mrow>",
+                    inner.concat()
+                ))
+            }
+            "right" => bail!("Unexpected \\right without \\left"),
+            "begin" => self.parse_environment(),
+            "end" => bail!("Unexpected \\end without \\begin"),
+            "\\" => Ok("<mspace linebreak=\"newline\"/>".to_string()),
+            _ => {
+                self.unknown.push(format!("\\{command}"));
+                Ok(format!("<merror><mtext>\\{}</mtext></merror>", escape(command)))
+            }
+        }
+    }
+
+    fn parse_environment(&mut self) -> Result<String, String> {
+        let name = self.parse_raw_group("begin")?;
+        let (open, close) = match name.as_str() {
+            "matrix" | "aligned" | "align" | "align*" | "array" | "gathered" => ("", ""),
+            "pmatrix" => ("(", ")"),
+            "bmatrix" => ("[", "]"),
+            "Bmatrix" => ("{", "}"),
+            "vmatrix" => ("|", "|"),
+            "Vmatrix" => ("&#x2016;", "&#x2016;"),
+            "cases" => ("{", ""),
+            _ => bail!("Unknown environment `{name}`"),
+        };
+        if name == "array" {
+            // column specification is irrelevant for MathML output
+            self.parse_raw_group("array")?;
+        }
+
+        let mut rows = vec![];
+        let mut cells = vec![];
+        loop {
+            let cell = self.parse_nodes(Until::Environment)?;
+            cells.push(format!("<mtd>{}</mtd>", cell.concat()));
+            if self.chars.peek() == Some(&'&') {
+                self.chars.next();
+            } else if self.eat_command("\\") {
+                rows.push(format!("<mtr>{}</mtr>", cells.concat()));
+                cells.clear();
+            } else if self.eat_command("end") {
+                let end = self.parse_raw_group("end")?;
+                if end != name {
+                    bail!("Environment `{name}` closed by \\end{{{end}}}");
+                }
+                if cells.iter().any(|cell| cell != "<mtd></mtd>") {
+                    rows.push(format!("<mtr>{}</mtr>", cells.concat()));
+                }
+                break;
+            } else {
+                bail!("Expected `&`, `\\\\` or \\end{{{name}}}");
+            }
+        }
+
+        let table = format!("<mtable>{}</mtable>", rows.concat());
+        if open.is_empty() && close.is_empty() {
+            return Ok(table);
+        }
+        let close = if close.is_empty() {
+            String::new()
+        } else {
+            format!("<mo fence=\"true\">{close}</mo>")
+        };
+        Ok(format!("<mrow><mo fence=\"true\">{open}</mo>{table}{close}</mrow>"))
+    }
+
+    fn parse_scripts(&mut self, base: String) -> Result<String, String> {
+        let mut sub = None;
+        let mut sup = None;
+        let mut limits = self.display && base.contains("movablelimits");
+        loop {
+            self.skip_whitespace();
+            if self.eat_command("limits") {
+                limits = true;
+                continue;
+            }
+            if self.eat_command("nolimits") {
+                limits = false;
+                continue;
+            }
+            match self.chars.peek() {
+                Some('_') if sub.is_none() => {
+                    self.chars.next();
+                    sub = Some(self.parse_script_argument()?);
+                }
+                Some('^') if sup.is_none() => {
+                    self.chars.next();
+                    sup = Some(self.parse_script_argument()?);
+                }
+                Some('\'') if sup.is_none() => {
+                    let mut primes = String::new();
+                    while self.chars.peek() == Some(&'\'') {
+                        self.chars.next();
+                        primes.push('′');
+                    }
+                    sup = Some(format!("<mo>{primes}</mo>"));
+                }
+                Some('_') | Some('^') => bail!("Double subscript or superscript"),
+                _ => break,
+            }
+        }
+
+        Ok(match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) if limits => format!("<munder>{base}{sub}</munder>"),
+            (None, Some(sup)) if limits => format!("<mover>{base}{sup}</mover>"),
+            (Some(sub), Some(sup)) if limits => format!("<munderover>{base}{sub}{sup}</munderover>"),
+            (Some(sub), None) => format!("<msub>{base}{sub}</msub>"),
+            (None, Some(sup)) => format!("<msup>{base}{sup}</msup>"),
+            (Some(sub), Some(sup)) => format!("<msubsup>{base}{sub}{sup}</msubsup>"),
+        })
+    }
+
+    fn parse_script_argument(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let c = self.chars.next().unwrap();
+                Ok(format!("<mn>{c}</mn>"))
+            }
+            Some(_) => self.parse_atom(),
+            None => bail!("Missing argument for script"),
+        }
+    }
+
+    fn parse_argument(&mut self, command: &str) -> Result<String, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('{') => {
+                self.chars.next();
+                self.parse_row(Until::Brace)
+            }
+            Some(_) => self.parse_atom(),
+            None => bail!("Missing argument for \\{command}"),
+        }
+    }
+
+    fn parse_raw_group(&mut self, command: &str) -> Result<String, String> {
+        self.skip_whitespace();
+        if self.chars.next() != Some('{') {
+            bail!("Expected `{{` after \\{command}");
+        }
+        let mut depth = 0;
+        let mut text = String::new();
+        for c in self.chars.by_ref() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => return Ok(text),
+                '}' => depth -= 1,
+                _ => {}
+            }
+            text.push(c);
+        }
+        bail!("Missing `}}` after \\{command}")
+    }
+
+    fn parse_delimiter(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        match self.next_token() {
+            Some(Token::Char('.')) => Ok(String::new()),
+            Some(Token::Char(c)) if "()[]|/".contains(c) => Ok(c.to_string()),
+            Some(Token::Command(command)) => match command.as_str() {
+                "{" => Ok("{".to_string()),
+                "}" => Ok("}".to_string()),
+                "|" => Ok("&#x2016;".to_string()),
+                _ => operator(&command)
+                    .map(str::to_string)
+                    .ok_or_else(|| format!("Invalid delimiter \\{command}")),
+            },
+            Some(Token::Char(c)) => bail!("Invalid delimiter `{c}`"),
+            None => bail!("Missing delimiter"),
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        let c = self.chars.next()?;
+        if c != '\\' {
+            return Some(Token::Char(c));
+        }
+        let mut command = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_ascii_alphabetic() {
+                break;
+            }
+            command.push(c);
+            self.chars.next();
+        }
+        if command.is_empty() {
+            command.push(self.chars.next()?);
+        }
+        Some(Token::Command(command))
+    }
+
+    /// Whether the next token is `\command`, e.g. `\end` but not `\endx`.
+    fn peek_command(&self, command: &str) -> bool {
+        let mut chars = self.chars.clone();
+        if chars.next() != Some('\\') || !chars.by_ref().take(command.len()).eq(command.chars()) {
+            return false;
+        }
+        let alphabetic = command.chars().all(|c| c.is_ascii_alphabetic());
+        !(alphabetic && chars.next().is_some_and(|c| c.is_ascii_alphabetic()))
+    }
+
+    fn eat_command(&mut self, command: &str) -> bool {
+        if !self.peek_command(command) {
+            return false;
+        }
+        for _ in 0..=command.len() {
+            self.chars.next();
+        }
+        true
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+}
+
+impl Until {
+    fn closing(&self) -> &'static str {
+        match self {
+            Until::End => "end of input",
+            Until::Brace => "`}`",
+            Until::Bracket => "`]`",
+            Until::Right => "\\right",
+            Until::Environment => "\\end",
+        }
+    }
+}
+
+fn mrow(nodes: Vec<String>) -> String {
+    if nodes.len() == 1 {
+        nodes.into_iter().next().unwrap()
+    } else {
+        format!("<mrow>{}</mrow>", nodes.concat())
+    }
+}
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh", "log", "ln", "lg", "exp", "det", "dim", "ker",
+    "deg", "arg", "gcd", "max", "min", "sup", "inf", "lim", "Pr",
+];
+
+fn identifier(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" => "ϵ",
+        "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" => "θ",
+        "vartheta" => "ϑ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" => "π",
+        "varpi" => "ϖ",
+        "rho" => "ρ",
+        "varrho" => "ϱ",
+        "sigma" => "σ",
+        "varsigma" => "ς",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" => "ϕ",
+        "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Upsilon" => "Υ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        "infty" => "∞",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "hbar" => "ℏ",
+        "ell" => "ℓ",
+        "emptyset" => "∅",
+        "aleph" => "ℵ",
+        _ => return None,
+    })
+}
+
+fn operator(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "pm" => "±",
+        "mp" => "∓",
+        "times" => "×",
+        "div" => "÷",
+        "cdot" => "⋅",
+        "ast" => "∗",
+        "circ" => "∘",
+        "bullet" => "∙",
+        "leq" | "le" => "≤",
+        "geq" | "ge" => "≥",
+        "neq" | "ne" => "≠",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "simeq" => "≃",
+        "cong" => "≅",
+        "propto" => "∝",
+        "ll" => "≪",
+        "gg" => "≫",
+        "in" => "∈",
+        "notin" => "∉",
+        "ni" => "∋",
+        "subset" => "⊂",
+        "subseteq" => "⊆",
+        "supset" => "⊃",
+        "supseteq" => "⊇",
+        "cup" => "∪",
+        "cap" => "∩",
+        "setminus" => "∖",
+        "vert" | "lvert" | "rvert" | "mid" => "|",
+        "Vert" | "lVert" | "rVert" => "‖",
+        "prime" => "′",
+        "wedge" | "land" => "∧",
+        "vee" | "lor" => "∨",
+        "neg" | "lnot" => "¬",
+        "forall" => "∀",
+        "exists" => "∃",
+        "to" | "rightarrow" => "→",
+        "leftarrow" | "gets" => "←",
+        "leftrightarrow" => "↔",
+        "Rightarrow" | "implies" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" | "iff" => "⇔",
+        "mapsto" => "↦",
+        "rightleftharpoons" => "⇌",
+        "uparrow" => "↑",
+        "downarrow" => "↓",
+        "ldots" | "dots" => "…",
+        "cdots" => "⋯",
+        "vdots" => "⋮",
+        "ddots" => "⋱",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "perp" => "⊥",
+        "parallel" => "∥",
+        "angle" => "∠",
+        "degree" => "°",
+        "{" => "{",
+        "}" => "}",
+        "|" => "‖",
+        "%" => "%",
+        "$" => "$",
+        "#" => "#",
+        "&" => "&amp;",
+        "_" => "_",
+        _ => return None,
+    })
+}
+
+fn large_operator(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "sum" => "∑",
+        "prod" => "∏",
+        "coprod" => "∐",
+        "int" => "∫",
+        "iint" => "∬",
+        "iiint" => "∭",
+        "oint" => "∮",
+        "bigcup" => "⋃",
+        "bigcap" => "⋂",
+        "bigoplus" => "⨁",
+        "bigotimes" => "⨂",
+        _ => return None,
+    })
+}
+
+fn space(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "," => "0.1667em",
+        ":" | ">" => "0.2222em",
+        ";" => "0.2778em",
+        "!" => "-0.1667em",
+        " " => "0.25em",
+        "quad" => "1em",
+        "qquad" => "2em",
+        _ => return None,
+    })
+}
+
+fn accent(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "hat" | "widehat" => "^",
+        "bar" | "overline" => "¯",
+        "vec" => "→",
+        "tilde" | "widetilde" => "~",
+        "dot" => "˙",
+        "ddot" => "¨",
+        _ => return None,
+    })
+}
+
+fn variant(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "mathrm" => "normal",
+        "mathbf" | "boldsymbol" => "bold",
+        "mathit" => "italic",
+        "mathbb" => "double-struck",
+        "mathcal" => "script",
+        "mathfrak" => "fraktur",
+        "mathsf" => "sans-serif",
+        "mathtt" => "monospace",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::{markdown::MarkdownError, page::Page};
+
+    fn body(tex: &str) -> String {
+        let mathml = latex_to_mathml(tex, false).unwrap().html;
+        let start = mathml.find("<semantics>").unwrap() + "<semantics>".len();
+        let end = mathml.find("<annotation").unwrap();
+        mathml[start..end].to_string()
+    }
+
+    #[test]
+    fn test_scripts() {
+        assert_eq!(body("x^2"), "<msup><mi>x</mi><mn>2</mn></msup>");
+        assert_eq!(
+            body("a_{i}^{n+1}"),
+            "<msubsup><mi>a</mi><mi>i</mi><mrow><mi>n</mi><mo>+</mo><mn>1</mn></mrow></msubsup>"
+        );
+    }
+
+    #[test]
+    fn test_fraction_and_root() {
+        assert_eq!(body("\\frac{1}{2}"), "<mfrac><mn>1</mn><mn>2</mn></mfrac>");
+        assert_eq!(body("\\sqrt[3]{x}"), "<mroot><mi>x</mi><mn>3</mn></mroot>");
+    }
+
+    #[test]
+    fn test_display_limits() {
+        let mathml = latex_to_mathml("\\sum_{i=0}^n i", true).unwrap().html;
+        assert!(mathml.contains("display=\"block\""));
+        assert!(mathml.contains("<munderover><mo movablelimits=\"true\">∑</mo>"));
+    }
+
+    #[test]
+    fn test_common_commands() {
+        assert_eq!(body("\\lvert x \\rvert"), "<mrow><mo>|</mo><mi>x</mi><mo>|</mo></mrow>");
+        assert_eq!(body("a \\mid b"), "<mrow><mi>a</mi><mo>|</mo><mi>b</mi></mrow>");
+        assert_eq!(body("f^\\prime"), "<msup><mi>f</mi><mo>′</mo></msup>");
+        assert_eq!(body("\\overset{!}{=}"), "<mover><mo>=</mo><mo>!</mo></mover>");
+        assert_eq!(body("\\displaystyle x"), "<mi>x</mi>");
+        assert!(body("\\sum\\limits_{i=0}^n").starts_with("<munderover><mo movablelimits=\"true\">∑</mo>"));
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let math = latex_to_mathml("x + \\foo", false).unwrap();
+        assert!(math.html.contains("<mo>+</mo><merror><mtext>\\foo</mtext></merror>"));
+        assert_eq!(math.unknown, ["\\foo"]);
+    }
+
+    #[test]
+    fn test_environment() {
+        assert_eq!(
+            body("\\begin{pmatrix} a & b \\\\ c & d \\end{pmatrix}"),
+            "<mrow><mo fence=\"true\">(</mo><mtable><mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr><mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd></mtr></mtable><mo fence=\"true\">)</mo></mrow>"
+        );
+    }
+
+    #[test]
+    fn test_left_right() {
+        assert_eq!(
+            body("\\left( x \\right)"),
+            "<mrow><mo fence=\"true\">(</mo><mi>x</mi><mo fence=\"true\">)</mo></mrow>"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(latex_to_mathml("\\frac{1}{2", false).unwrap_err().to_string().contains("missing `}`"));
+
+        assert!(latex_to_mathml("x}", false).is_err());
+    }
+
+    #[test]
+    fn test_environment_errors() {
+        let err = latex_to_mathml("\\begin{matrix}a\\endx", false).unwrap_err();
+        assert_eq!(err, "Unexpected end of expression, missing \\end");
+        let err = latex_to_mathml("\\begin{matrix}a\\ends{matrix}", false).unwrap_err();
+        assert_eq!(err, "Unexpected end of expression, missing \\end");
+        assert!(latex_to_mathml("\\begin{matrix}a", false).is_err());
+    }
+
+    #[test]
+    fn test_truncated_input_terminates() {
+        let tex = "\\left( \\begin{cases} \\frac{a}{b} & x \\\\ \\sqrt[3]{y_i^2} \\end{cases} \\right) \\text{ok}";
+        for end in (0..=tex.len()).filter(|end| tex.is_char_boundary(*end)) {
+            let _ = latex_to_mathml(&tex[..end], false);
+            let _ = latex_to_mathml(&format!("{}x", &tex[..end]), true);
+        }
+    }
+
+    #[test]
+    fn test_markdown_math() {
+        let page = Page::from_string("Inline $x^2$ and\n\n```math\n\\frac{a}{b}\n```").unwrap();
+        assert!(
+            page.content
+                .contains("<p>Inline <math xmlns=\"http://www.w3.org/1998/Math/MathML\"><semantics><msup>")
+        );
+        assert!(page.content.contains("display=\"block\"><semantics><mfrac>"));
+
+        let err = Page::from_string("# Title\n\nSome $\\frac{1}$ math").unwrap_err();
+        assert!(matches!(
+            err,
+            crate::Error::Markdown {
+                source: MarkdownError::Math { line: 3, .. },
+                ..
+            }
+        ));
+        assert_eq!(
+            err.chain().nth(1).unwrap().to_string(),
+            "Invalid math expression at line 3: Missing argument for \\frac"
+        );
+    }
+
+    #[test]
+    fn test_katex() {
+        let html = render_math("a < b", false, MathRenderer::KaTeX).unwrap().html;
+        assert_eq!(html, "<span class=\"math inline\">\\(a &lt; b\\)</span>");
+    }
+}
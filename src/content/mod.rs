@@ -1,23 +1,58 @@
+use crate::{Error, Result};
+use markdown::RenderContext;
 use page::Page;
 use std::{collections::HashMap, path::PathBuf};
 
+pub mod admonitions;
+pub mod cascade;
+pub mod diagrams;
 pub mod frontmatter;
+pub mod git;
+pub mod links;
 pub mod markdown;
+pub mod math;
 pub mod page;
+pub mod shortcodes;
+pub mod site;
 
 pub const COLOR_PICKER_JS: &str = include_str!("../builtins/SwitchColorMode.js");
 
-pub fn read_files(root: &PathBuf) -> anyhow::Result<HashMap<PathBuf, Page>> {
+/// 64-bit FNV-1a hash of `bytes`. Unlike `DefaultHasher` it is stable across Rust releases and
+/// thus usable for file names of persistent caches and cache busting URLs.
+pub(crate) fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Escapes `text` for HTML and MathML element content.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+pub fn read_files(root: &PathBuf, ctx: &RenderContext) -> Result<HashMap<PathBuf, Page>> {
     let mut map = HashMap::new();
     for entry in walkdir::WalkDir::new(root) {
-        let entry = entry?;
+        let entry = entry.map_err(|err| Error::io(root)(err.into()))?;
 
         if entry.file_type().is_file() && entry.path().extension().map(|ext| ext == "md").unwrap_or(false) {
             let path = entry.path().to_path_buf();
-            let page = Page::read(&path)?;
+            let page = Page::read(&path, ctx)?;
             map.insert(path.clone(), page);
         }
     }
 
     Ok(map)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stable_hash() {
+        assert_eq!(stable_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stable_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(stable_hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+}
@@ -1,17 +1,41 @@
-use super::{frontmatter::Frontmatter, markdown::render_html};
-use crate::config::AppConfig;
+use super::{
+    frontmatter::{Frontmatter, FrontmatterMode},
+    markdown::{RenderContext, render_html},
+    shortcodes::{Shortcodes, render_shortcodes, strip_placeholders},
+};
+use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, fs, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
 
 #[derive(Default, Clone, Debug, Serialize)]
 pub struct Page {
     #[serde(flatten)]
     pub frontmatter: Frontmatter,
     pub outline: Vec<PageHeading>,
+    pub summary: Option<Summary>,
+    #[serde(flatten)]
+    pub stats: PageStats,
     #[serde(skip_serializing)]
     pub content: String,
 }
 
+#[derive(Default, Clone, Copy, Debug, Serialize)]
+pub struct PageStats {
+    pub word_count: usize,
+    /// Estimated reading time in minutes
+    pub reading_time: usize,
+}
+
+#[derive(Default, Clone, Debug, Serialize)]
+pub struct Summary {
+    pub html: String,
+    pub text: String,
+}
+
 #[derive(Default, Clone, Debug, Serialize)]
 pub struct PageHeading {
     pub level: u8,
@@ -21,22 +45,61 @@ pub struct PageHeading {
 }
 
 impl Page {
-    pub fn read(path: &PathBuf) -> anyhow::Result<Self> {
-        let content = fs::read_to_string(path)?;
-        Page::from_string(&content)
+    pub fn read(path: &PathBuf, ctx: &RenderContext) -> Result<Self> {
+        let content = fs::read_to_string(path).map_err(Error::io(path))?;
+        let ctx = RenderContext {
+            path: Some(path.clone()),
+            ..ctx.clone()
+        };
+        Page::render(&content, &ctx)
+    }
+
+    pub fn from_string(content: &str) -> Result<Self> {
+        Page::render(content, &RenderContext::default())
     }
 
-    pub fn from_string(content: &str) -> anyhow::Result<Self> {
+    pub fn render(content: &str, ctx: &RenderContext) -> Result<Self> {
         let mut headings = Vec::new();
-        let mut frontmatter = Frontmatter::read(content).unwrap_or_default();
+        let mut summary = None;
+        let mut stats = PageStats::default();
+        let path = ctx.path.as_deref();
+        let mut frontmatter = match ctx.config.frontmatter {
+            FrontmatterMode::Strict => Frontmatter::read(content).map_err(|err| err.with_path(path))?,
+            FrontmatterMode::Lenient => {
+                let (frontmatter, errors) = Frontmatter::read_lenient(content);
+                for err in errors {
+                    warn!("{}", err.with_path(path));
+                }
+                frontmatter
+            }
+        }
+        .unwrap_or_default();
 
-        let html = render_html(content, &mut headings, &mut frontmatter)?;
+        let shortcodes = match ctx.tera {
+            Some(tera) => render_shortcodes(content, tera, ctx.data).map_err(|source| Error::Markdown {
+                path: ctx.path.clone(),
+                source,
+            })?,
+            None => Shortcodes::new(content),
+        };
+        let html = render_html(&shortcodes.content, ctx, &mut headings, &mut summary, &mut stats, &mut frontmatter)?;
+        let html = shortcodes.restore(&html);
+        if let Some(summary) = &mut summary {
+            summary.html = shortcodes.restore(&summary.html);
+            summary.text = strip_placeholders(&summary.text);
+        }
         headings = build_tree(&headings);
 
+        if frontmatter.description.is_none() {
+            frontmatter.description = summary.as_ref().map(|summary| summary.text.clone());
+        }
+
         Ok(Page {
             frontmatter,
             content: html,
             outline: headings,
+            summary,
+            stats,
         })
     }
 }
@@ -47,35 +110,56 @@ pub struct NavItem {
     pub title: String,
 }
 
-pub fn filename_to_url(filename: &PathBuf, config: &AppConfig) -> String {
-    let content_dir = config.folder.join("content");
-    let relative_to_root = &pathdiff::diff_paths(filename, content_dir).unwrap_or(filename.to_owned());
-    let folder = relative_to_root.parent().unwrap_or(relative_to_root);
-    let filename = relative_to_root.file_stem().unwrap(); //we are sure it is a file
-    format!("/{}", folder.join(filename).to_string_lossy())
+/// URL of the page rendered from `relative`, a markdown file relative to the content directory.
+pub fn content_url(relative: &Path) -> String {
+    let mut parts = relative
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    if parts.last().is_some_and(|last| last == "index") {
+        parts.pop();
+        return parts.iter().fold("/".to_string(), |url, part| format!("{url}{part}/"));
+    }
+    format!("/{}", parts.join("/"))
 }
 
 fn build_tree(flat: &[PageHeading]) -> Vec<PageHeading> {
     let mut tree = vec![];
-    let mut last_item: Option<&mut PageHeading> = None;
-
     for item in flat {
-        if let Some(last) = &mut last_item {
-            match item.level.cmp(&last.level) {
-                Ordering::Greater => last.children.push(item.clone()),
-                _ => tree.push(item.clone()),
-            }
-        } else {
-            tree.push(item.clone());
-        }
-        last_item = tree.last_mut();
+        insert_heading(&mut tree, item.clone());
     }
     tree
 }
 
+/// Nests `item` under the last sibling with a lower level, recursively.
+fn insert_heading(siblings: &mut Vec<PageHeading>, item: PageHeading) {
+    match siblings.last_mut() {
+        Some(last) if item.level > last.level => insert_heading(&mut last.children, item),
+        _ => siblings.push(item),
+    }
+}
+
+/// Restricts the outline to `min_level` and the `depth` levels below it. Headings above
+/// `min_level` are dropped and their children take their place.
+pub fn limit_outline(outline: &[PageHeading], min_level: u8, depth: Option<u8>) -> Vec<PageHeading> {
+    let max_level = depth.map(|depth| min_level.saturating_add(depth).saturating_sub(1)).unwrap_or(u8::MAX);
+    let mut limited = vec![];
+    for heading in outline {
+        let children = limit_outline(&heading.children, min_level, depth);
+        if heading.level < min_level {
+            limited.extend(children);
+        } else if heading.level <= max_level {
+            limited.push(PageHeading { children, ..heading.clone() });
+        }
+    }
+    limited
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::MarkdownConfig;
 
     #[test]
     fn test_page_from_string() {
@@ -97,6 +181,140 @@ Hello, world
         assert_eq!(page.outline[0].id, "test-heading");
     }
 
+    #[test]
+    fn test_page_toml_frontmatter() {
+        let err = Page::from_string("+++\ntitle = \"Test Title\"\n+++\n\n# Test Heading\n\n$\\frac{1}{$").unwrap_err();
+        assert_eq!(
+            err.chain().nth(1).unwrap().to_string(),
+            "Invalid math expression at line 7: Unexpected end of expression, missing `}`"
+        );
+
+        let page = Page::from_string("+++\ntitle = \"Test Title\"\n+++\n\n# Test Heading").unwrap();
+        assert_eq!(page.frontmatter.title, Some("Test Title".to_string()));
+        assert!(!page.content.contains("title"));
+    }
+
+    #[test]
+    fn test_summary_more_marker() {
+        let content = "# Title\n\nFirst *paragraph*.\n\nSecond paragraph.\n\n<!-- more -->\n\nRest of the post.";
+        let page = Page::from_string(content).unwrap();
+        let summary = page.summary.unwrap();
+        assert_eq!(summary.html, "<p>First <em>paragraph</em>.</p>\n<p>Second paragraph.</p>\n");
+        assert_eq!(summary.text, "First paragraph. Second paragraph.");
+        assert_eq!(page.frontmatter.description, Some(summary.text));
+    }
+
+    #[test]
+    fn test_summary_first_paragraph() {
+        let ctx = RenderContext {
+            config: MarkdownConfig {
+                summary_words: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let page = Page::render("---\ndescription: Given\n---\n# Title\n\nOne two three four.\n\nSecond.", &ctx).unwrap();
+        let summary = page.summary.unwrap();
+        assert_eq!(summary.html, "<p>One two three four.</p>\n");
+        assert_eq!(summary.text, "One two three…");
+        assert_eq!(page.frontmatter.description, Some("Given".to_string()));
+    }
+
+    #[test]
+    fn test_page_stats() {
+        let content = format!("# Title\n\n{}\n\n```\n{}\n```", "word ".repeat(399), "code ".repeat(200));
+        let page = Page::from_string(&content).unwrap();
+        assert_eq!(page.stats.word_count, 400);
+        assert_eq!(page.stats.reading_time, 2);
+
+        let ctx = RenderContext {
+            config: MarkdownConfig {
+                words_per_minute: Some(100),
+                code_weight: Some(0.5),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let page = Page::render(&content, &ctx).unwrap();
+        assert_eq!(page.stats.word_count, 400);
+        assert_eq!(page.stats.reading_time, 5);
+    }
+
+    #[test]
+    fn test_raw_html() {
+        let content = "<div class=\"box\">Hi</div>\n\nText <b>bold</b>\n\n<!-- more -->";
+        let page = Page::from_string(content).unwrap();
+        assert_eq!(
+            page.content,
+            "<!-- raw HTML omitted -->\n<p>Text <!-- raw HTML omitted -->bold<!-- raw HTML omitted --></p>\n<!-- more -->\n"
+        );
+
+        let ctx = RenderContext {
+            config: MarkdownConfig {
+                raw_html: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let page = Page::render(content, &ctx).unwrap();
+        assert!(page.content.starts_with("<div class=\"box\">Hi</div>\n<p>Text <b>bold</b></p>"));
+    }
+
+    #[test]
+    fn test_dangerous_urls() {
+        let page = Page::from_string("[x](javascript:alert(1)) ![i](data:text/html,boom) ![ok](data:image/png;base64,AA)").unwrap();
+        assert_eq!(
+            page.content,
+            "<p><a href=\"\">x</a> <img src=\"\" alt=\"i\" /> <img src=\"data:image/png;base64,AA\" alt=\"ok\" /></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_shortcode_output_is_not_parsed() {
+        let mut tera = tera::Tera::default();
+        tera.add_raw_template("shortcodes/video.html", "<video>\n\n<source src=\"{{ src }}\">\n\n</video>")
+            .unwrap();
+        let ctx = RenderContext {
+            tera: Some(&tera),
+            ..Default::default()
+        };
+        let page = Page::render("# Title\n\n{{ video(src='a.mp4') }}\n\nSee {{ video(src='b.mp4') }}", &ctx).unwrap();
+        assert!(
+            page.content
+                .contains("</h1>\n<video>\n\n<source src=\"a.mp4\">\n\n</video>\n<p>See <video>")
+        );
+
+        let err = Page::render("{{ video(src='a.mp4') }}\n\n$\\frac{1}{$", &ctx).unwrap_err();
+        assert_eq!(
+            err.chain().nth(1).unwrap().to_string(),
+            "Invalid math expression at line 3: Unexpected end of expression, missing `}`"
+        );
+    }
+
+    #[test]
+    fn test_shortcodes_in_headings_and_links() {
+        let mut tera = tera::Tera::default();
+        tera.add_raw_template("shortcodes/icon.html", "<i class=\"icon\"></i>").unwrap();
+        tera.add_raw_template("shortcodes/url.html", "/docs/").unwrap();
+        let ctx = RenderContext {
+            tera: Some(&tera),
+            ..Default::default()
+        };
+        let page = Page::render("# Hello {{ icon() }}\n\n[x]({{ url() }}) ![i]({{ url() }})", &ctx).unwrap();
+        assert_eq!(page.frontmatter.title.as_deref(), Some("Hello"));
+        assert_eq!((page.outline[0].id.as_str(), page.outline[0].title.as_str()), ("hello", "Hello"));
+        assert_eq!(page.stats.word_count, 3);
+        assert!(page.content.contains("Hello <i class=\"icon\"></i></h1>"), "{}", page.content);
+        assert!(page.content.contains("<a href=\"/docs/\">x</a> <img src=\"/docs/\" alt=\"i\" />"));
+    }
+
+    #[test]
+    fn test_content_url() {
+        assert_eq!(content_url(Path::new("index.md")), "/");
+        assert_eq!(content_url(Path::new("docs/index.md")), "/docs/");
+        assert_eq!(content_url(Path::new("docs/guide/intro.md")), "/docs/guide/intro");
+    }
+
     #[test]
     fn test_page_auto_title() {
         let content = r#"---
@@ -111,4 +329,93 @@ Hello, world
         assert_eq!(page.frontmatter.title, Some("Test Heading".to_string()));
         assert_eq!(page.frontmatter.description, Some("Test Description".to_string()));
     }
+
+    #[test]
+    fn test_unique_heading_ids() {
+        let page = Page::from_string("# Example\n## Example\n## Other {#example-1}").unwrap();
+        let ids = [&page.outline[0].id, &page.outline[0].children[0].id, &page.outline[0].children[1].id];
+        assert_eq!(ids, ["example", "example-2", "example-1"]);
+        assert!(
+            page.content
+                .contains("<h2><a href=\"#example-2\" aria-hidden=\"true\" class=\"anchor\" id=\"example-2\"></a>Example</h2>")
+        );
+    }
+
+    #[test]
+    fn test_custom_heading_id() {
+        let page = Page::from_string("## Install `ferrocyanide` {#setup}").unwrap();
+        assert_eq!(page.outline[0].id, "setup");
+        assert_eq!(page.outline[0].title, "Install ferrocyanide");
+        assert!(page.content.contains("id=\"setup\"></a>Install <code>ferrocyanide</code></h2>"));
+
+        let page = Page::from_string("## Title {#x\"onmouseover=\"alert(1)}").unwrap();
+        assert_eq!(page.outline[0].id, "title-xonmouseoveralert1");
+        assert!(!page.content.contains("onmouseover=\""), "{}", page.content);
+        assert!(page.content.contains("</a>Title {#x&quot;onmouseover=&quot;alert(1)}</h2>"));
+    }
+
+    #[test]
+    fn test_heading_anchor() {
+        let ctx = RenderContext {
+            config: MarkdownConfig {
+                heading_anchor: Some("¶".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let page = Page::render("# Title", &ctx).unwrap();
+        assert!(
+            page.content
+                .contains("Title <a href=\"#title\" class=\"heading-anchor\" aria-label=\"Link to this section\">¶</a></h1>")
+        );
+    }
+
+    fn heading(level: u8, id: &str) -> PageHeading {
+        PageHeading {
+            level,
+            id: id.to_string(),
+            title: id.to_string(),
+            children: vec![],
+        }
+    }
+
+    fn ids(outline: &[PageHeading]) -> String {
+        outline
+            .iter()
+            .map(|h| {
+                if h.children.is_empty() {
+                    h.id.clone()
+                } else {
+                    format!("{}({})", h.id, ids(&h.children))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    #[test]
+    fn test_build_tree() {
+        let flat = [
+            heading(1, "a"),
+            heading(2, "b"),
+            heading(3, "c"),
+            heading(4, "d"),
+            heading(2, "e"),
+            heading(4, "f"),
+            heading(1, "g"),
+        ];
+        assert_eq!(ids(&build_tree(&flat)), "a(b(c(d)),e(f)),g");
+
+        let skipped = [heading(2, "a"), heading(1, "b"), heading(3, "c")];
+        assert_eq!(ids(&build_tree(&skipped)), "a,b(c)");
+    }
+
+    #[test]
+    fn test_limit_outline() {
+        let outline = build_tree(&[heading(1, "a"), heading(2, "b"), heading(3, "c"), heading(4, "d"), heading(2, "e")]);
+        assert_eq!(ids(&limit_outline(&outline, 1, None)), "a(b(c(d)),e)");
+        assert_eq!(ids(&limit_outline(&outline, 2, None)), "b(c(d)),e");
+        assert_eq!(ids(&limit_outline(&outline, 2, Some(2))), "b(c),e");
+        assert_eq!(ids(&limit_outline(&outline, 1, Some(1))), "a");
+    }
 }
@@ -0,0 +1,437 @@
+use super::markdown::{MarkdownError, verbatim_ranges};
+use std::ops::Range;
+use tera::{Context, Map, Number, Tera, Value};
+
+/// Returns a [`MarkdownError::Shortcode`] with the formatted message.
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err(MarkdownError::Shortcode(format!($($arg)*)))
+    };
+}
+
+/// Resolves inline `{{ name(arg=...) }}` and block `{% name(arg=...) %}...{% end %}` shortcodes
+/// by rendering `shortcodes/<name>.html`. Frontmatter, fenced code blocks and code spans are left
+/// untouched, `{{/* ... */}}` and `{%/* ... */%}` emit the invocation literally.
+pub(crate) fn render_shortcodes(content: &str, tera: &Tera, data: Option<&serde_json::Value>) -> Result<Shortcodes, MarkdownError> {
+    let mut parser = Parser {
+        src: content,
+        pos: 0,
+        tera,
+        data,
+        verbatim: verbatim_ranges(content),
+        outputs: vec![],
+    };
+    let content = parser.render(None)?;
+    Ok(Shortcodes {
+        content,
+        outputs: parser.outputs,
+    })
+}
+
+/// Markdown with each shortcode replaced by a placeholder. The rendered shortcodes are only put
+/// back into the final HTML, so markdown never parses their output and line numbers of the
+/// content stay intact.
+#[derive(Debug, Default)]
+pub(crate) struct Shortcodes {
+    pub content: String,
+    outputs: Vec<String>,
+}
+
+impl Shortcodes {
+    pub fn new(content: &str) -> Self {
+        Shortcodes {
+            content: content.to_string(),
+            outputs: vec![],
+        }
+    }
+
+    /// Replaces the placeholders in `html` with the shortcode output, a placeholder that makes
+    /// up a whole paragraph replaces the paragraph. Placeholders in link and image destinations
+    /// are percent-encoded by comrak.
+    pub fn restore(&self, html: &str) -> String {
+        let mut html = html.to_string();
+        for (index, output) in self.outputs.iter().enumerate() {
+            let placeholder = placeholder(index);
+            let encoded = format!("%EE%80%80{index}%EE%80%81");
+            html = html
+                .replace(&format!("<p>{placeholder}</p>"), output)
+                .replace(&placeholder, output)
+                .replace(&encoded, output.trim());
+        }
+        html
+    }
+}
+
+/// Private use characters, which markdown passes through untouched.
+fn placeholder(index: usize) -> String {
+    format!("{PLACEHOLDER_START}{index}{PLACEHOLDER_END}")
+}
+
+const PLACEHOLDER_START: char = '\u{E000}';
+const PLACEHOLDER_END: char = '\u{E001}';
+
+/// Removes shortcode placeholders from plain text like titles, heading ids and the summary and
+/// collapses the whitespace left behind.
+pub(crate) fn strip_placeholders(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_placeholder = false;
+    for c in text.chars() {
+        match c {
+            PLACEHOLDER_START => in_placeholder = true,
+            PLACEHOLDER_END => in_placeholder = false,
+            c if !in_placeholder => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    tera: &'a Tera,
+    /// Site data, available to shortcodes as `data` like in page templates
+    data: Option<&'a serde_json::Value>,
+    verbatim: Vec<Range<usize>>,
+    outputs: Vec<String>,
+}
+
+struct Call {
+    name: String,
+    args: Map<String, Value>,
+    start: usize,
+}
+
+enum Tag {
+    Call(Call),
+    End,
+}
+
+impl<'a> Parser<'a> {
+    fn render(&mut self, block_start: Option<usize>) -> Result<String, MarkdownError> {
+        let mut out = String::new();
+        loop {
+            if let Some(range) = self.verbatim.iter().find(|r| r.start == self.pos) {
+                out.push_str(&self.src[range.clone()]);
+                self.pos = range.end;
+                continue;
+            }
+
+            let rest = &self.src[self.pos..];
+            if rest.is_empty() {
+                if let Some(start) = block_start {
+                    bail!("Unclosed shortcode at {}, expected {{% end %}}", self.location(start));
+                }
+                return Ok(out);
+            }
+
+            if let Some((open, close)) = [("{{/*", "*/}}"), ("{%/*", "*/%}")].into_iter().find(|(open, _)| rest.starts_with(open)) {
+                let end = rest
+                    .find(close)
+                    .ok_or_else(|| MarkdownError::Shortcode(format!("Unclosed shortcode escape at {}", self.location(self.pos))))?;
+                out.push_str(&open[..2]);
+                out.push_str(&rest[open.len()..end]);
+                out.push_str(&close[2..]);
+                self.pos += end + close.len();
+                continue;
+            }
+
+            if rest.starts_with("{{") {
+                if let Some(Tag::Call(call)) = self.parse_tag("}}")? {
+                    let output = self.render_call(&call, None)?.trim_end().to_string();
+                    self.emit(&mut out, output, call.start, block_start.is_some());
+                    continue;
+                }
+            } else if rest.starts_with("{%") {
+                let start = self.pos;
+                if let Some(tag) = self.parse_tag("%}")? {
+                    match tag {
+                        Tag::Call(call) => {
+                            let body = self.render(Some(call.start))?;
+                            let output = self.render_call(&call, Some(body))?;
+                            self.emit(&mut out, output, call.start, block_start.is_some());
+                        }
+                        Tag::End if block_start.is_some() => return Ok(out),
+                        Tag::End => bail!("Unexpected {{% end %}} at {}", self.location(start)),
+                    }
+                    continue;
+                }
+            }
+
+            let next = rest.chars().next().unwrap();
+            out.push(next);
+            self.pos += next.len_utf8();
+        }
+    }
+
+    /// Output of nested shortcodes becomes part of the body of the enclosing block, top level
+    /// shortcodes are replaced by a placeholder followed by the newlines of the invocation.
+    fn emit(&mut self, out: &mut String, output: String, start: usize, nested: bool) {
+        if nested {
+            out.push_str(&output);
+            return;
+        }
+        out.push_str(&placeholder(self.outputs.len()));
+        out.push_str(&"\n".repeat(self.src[start..self.pos].matches('\n').count()));
+        self.outputs.push(output);
+    }
+
+    /// Parses a tag at the current position. Returns `None` if the text does not look like a
+    /// shortcode at all, so that e.g. `{{ foo }}` is kept as is.
+    fn parse_tag(&mut self, close: &str) -> Result<Option<Tag>, MarkdownError> {
+        let start = self.pos;
+        self.pos += 2;
+        self.skip_whitespace();
+        let name = self.parse_ident();
+        self.skip_whitespace();
+
+        if name == "end" && close == "%}" && self.eat(close) {
+            return Ok(Some(Tag::End));
+        }
+        if name.is_empty() || !self.eat("(") {
+            self.pos = start;
+            return Ok(None);
+        }
+
+        let mut args = Map::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat(")") {
+                break;
+            }
+            let key = self.parse_ident();
+            if key.is_empty() {
+                bail!("Expected argument name in shortcode `{name}` at {}", self.location(self.pos));
+            }
+            self.skip_whitespace();
+            if !self.eat("=") {
+                bail!("Expected `=` after argument `{key}` in shortcode `{name}` at {}", self.location(self.pos));
+            }
+            self.skip_whitespace();
+            let value = self
+                .parse_value()
+                .map_err(|err| MarkdownError::Shortcode(format!("Invalid value for argument `{key}` in shortcode `{name}`: {err}")))?;
+            args.insert(key, value);
+            self.skip_whitespace();
+            if !self.eat(",") && !self.rest().starts_with(')') {
+                bail!("Expected `,` or `)` in shortcode `{name}` at {}", self.location(self.pos));
+            }
+        }
+
+        self.skip_whitespace();
+        if !self.eat(close) {
+            bail!("Expected `{close}` to close shortcode `{name}` at {}", self.location(self.pos));
+        }
+        Ok(Some(Tag::Call(Call { name, args, start })))
+    }
+
+    fn parse_value(&mut self) -> Result<Value, MarkdownError> {
+        let rest = self.rest();
+        let location = self.location(self.pos);
+
+        if let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            let mut value = String::new();
+            let mut chars = rest.char_indices().skip(1);
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some((_, escaped)) = chars.next() {
+                            value.push(escaped);
+                        }
+                    }
+                    c if c == quote => {
+                        self.pos += i + 1;
+                        return Ok(Value::String(value));
+                    }
+                    c => value.push(c),
+                }
+            }
+            bail!("Unterminated string at {location}");
+        }
+
+        if self.eat("[") {
+            let mut items = vec![];
+            loop {
+                self.skip_whitespace();
+                if self.eat("]") {
+                    return Ok(Value::Array(items));
+                }
+                items.push(self.parse_value()?);
+                self.skip_whitespace();
+                if !self.eat(",") && !self.rest().starts_with(']') {
+                    bail!("Expected `,` or `]` at {}", self.location(self.pos));
+                }
+            }
+        }
+
+        let literal = self.parse_literal();
+        match literal {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => {
+                if let Ok(int) = literal.parse::<i64>() {
+                    Ok(Value::Number(int.into()))
+                } else if let Some(float) = literal.parse::<f64>().ok().and_then(Number::from_f64) {
+                    Ok(Value::Number(float))
+                } else {
+                    bail!("Unexpected value `{literal}` at {location}")
+                }
+            }
+        }
+    }
+
+    fn render_call(&self, call: &Call, body: Option<String>) -> Result<String, MarkdownError> {
+        let template = format!("shortcodes/{}.html", call.name);
+        if !self.tera.get_template_names().any(|name| name == template) {
+            return Err(MarkdownError::UnknownShortcode {
+                name: call.name.clone(),
+                location: self.location(call.start),
+            });
+        }
+
+        let mut context = Context::new();
+        if let Some(data) = self.data {
+            context.insert("data", data);
+        }
+        for (key, value) in &call.args {
+            context.insert(key, value);
+        }
+        if let Some(body) = body {
+            context.insert("body", &body);
+        }
+
+        self.tera.render(&template, &context).map_err(|source| MarkdownError::Template {
+            message: format!("Failed to render shortcode `{}` at {}", call.name, self.location(call.start)),
+            source,
+        })
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn parse_ident(&mut self) -> String {
+        let rest = self.rest();
+        let len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-')).unwrap_or(rest.len());
+        self.pos += len;
+        rest[..len].to_string()
+    }
+
+    fn parse_literal(&mut self) -> &'a str {
+        let start = self.pos;
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || c == ',' || c == ')' || c == ']')
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &self.src[start..start + len]
+    }
+
+    fn location(&self, offset: usize) -> String {
+        let before = &self.src[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+        format!("line {line}, column {column}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tera() -> Tera {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            (
+                "shortcodes/youtube.html",
+                "<iframe src=\"https://youtube.com/embed/{{ id }}\"></iframe>\n",
+            ),
+            (
+                "shortcodes/callout.html",
+                "<div class=\"{{ kind | default(value='note') }}\">{{ body | safe }}</div>",
+            ),
+        ])
+        .unwrap();
+        tera
+    }
+
+    fn render(content: &str) -> String {
+        let shortcodes = render_shortcodes(content, &tera(), None).unwrap();
+        shortcodes.restore(&shortcodes.content)
+    }
+
+    #[test]
+    fn test_inline_shortcode() {
+        let shortcodes = render_shortcodes("Watch {{ youtube(id=\"abc\") }} now", &tera(), None).unwrap();
+        assert_eq!(shortcodes.content, format!("Watch {} now", placeholder(0)));
+        assert_eq!(
+            shortcodes.restore(&format!("<p>Watch {} now</p>", placeholder(0))),
+            "<p>Watch <iframe src=\"https://youtube.com/embed/abc\"></iframe> now</p>"
+        );
+        assert_eq!(strip_placeholders(&format!("Watch {} now", placeholder(0))), "Watch now");
+    }
+
+    #[test]
+    fn test_block_shortcode() {
+        let result = render("{% callout(kind='tip') %}Hi {% callout() %}there{% end %}{% end %}");
+        assert_eq!(result, "<div class=\"tip\">Hi <div class=\"note\">there</div></div>");
+    }
+
+    #[test]
+    fn test_shortcodes_keep_line_numbers() {
+        let shortcodes = render_shortcodes("{% callout() %}\nline 2\n{% end %}\ntext", &tera(), None).unwrap();
+        assert_eq!(shortcodes.content, format!("{}\n\n\ntext", placeholder(0)));
+        let html = format!("<p>{}</p>\n<p>text</p>", placeholder(0));
+        assert_eq!(shortcodes.restore(&html), "<div class=\"note\">\nline 2\n</div>\n<p>text</p>");
+    }
+
+    #[test]
+    fn test_non_shortcodes_are_kept() {
+        let content = "---\ntitle: \"{{ youtube(id=1) }}\"\n---\n{{ not_a_call }}\n```\n{{ youtube(id=1) }}\n```\n{{/* youtube(id=1) */}}";
+        assert_eq!(
+            render(content),
+            "---\ntitle: \"{{ youtube(id=1) }}\"\n---\n{{ not_a_call }}\n```\n{{ youtube(id=1) }}\n```\n{{ youtube(id=1) }}"
+        );
+        let code_spans = "Use `{{ youtube(id=1) }}` or ``{% callout() %}`` to embed";
+        assert_eq!(render(code_spans), code_spans);
+    }
+
+    #[test]
+    fn test_unknown_shortcode() {
+        let err = render_shortcodes("text\n  {{ vimeo(id=1) }}", &tera(), None).unwrap_err();
+        assert!(matches!(&err, MarkdownError::UnknownShortcode { name, .. } if name == "vimeo"));
+        assert_eq!(
+            err.to_string(),
+            "Unknown shortcode `vimeo` at line 2, column 3, expected template shortcodes/vimeo.html"
+        );
+    }
+
+    #[test]
+    fn test_bad_argument() {
+        let err = render_shortcodes("{{ youtube(id=abc) }}", &tera(), None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid value for argument `id` in shortcode `youtube`: Unexpected value `abc` at line 1, column 15"
+        );
+    }
+
+    #[test]
+    fn test_unclosed_block() {
+        let err = render_shortcodes("{% callout() %}body", &tera(), None).unwrap_err();
+        assert!(err.to_string().contains("Unclosed shortcode at line 1, column 1"));
+    }
+}
@@ -0,0 +1,242 @@
+use super::page::{Page, content_url};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+use tera::Value;
+
+/// Read-only view of all pages for the site-wide template functions. Paths are relative to the
+/// content directory, e.g. `blog/first-post.md` or `blog` for a section.
+#[derive(Clone, Debug)]
+pub struct Site {
+    library: Arc<HashMap<PathBuf, Page>>,
+    content_dir: PathBuf,
+    url_prefix: String,
+}
+
+impl Site {
+    pub fn new(library: Arc<HashMap<PathBuf, Page>>, content_dir: PathBuf, url_prefix: String) -> Self {
+        Site {
+            library,
+            content_dir,
+            url_prefix,
+        }
+    }
+
+    /// Final URL of the page at `path`.
+    pub fn url(&self, path: &str) -> tera::Result<String> {
+        let file = self.content_dir.join(path.trim_start_matches('/'));
+        if !self.library.contains_key(&file) {
+            return Err(format!("`{path}` is not a page").into());
+        }
+        Ok(self.page_url(&file))
+    }
+
+    pub fn page(&self, path: &str) -> tera::Result<Value> {
+        let file = self.content_dir.join(path.trim_start_matches('/'));
+        let page = self.library.get(&file).ok_or_else(|| format!("`{path}` is not a page"))?;
+        self.page_value(&file, page)
+    }
+
+    /// The section of directory `path` with its `index` page, the pages directly inside it and
+    /// the paths of its subsections.
+    pub fn section(&self, path: &str) -> tera::Result<Value> {
+        let dir = self.content_dir.join(path.trim_matches('/'));
+        let index = dir.join("index.md");
+
+        let mut pages = vec![];
+        let mut subsections = BTreeSet::new();
+        for (file, page) in self.sorted() {
+            let Ok(relative) = file.strip_prefix(&dir) else {
+                continue;
+            };
+            match relative.components().collect::<Vec<_>>().as_slice() {
+                [_] if *file != index => pages.push(self.page_value(file, page)?),
+                [Component::Normal(name), _, ..] => {
+                    subsections.insert(self.relative(&dir.join(name)));
+                }
+                _ => {}
+            }
+        }
+
+        let index = self.library.get(&index).map(|page| self.page_value(&index, page)).transpose()?;
+        if index.is_none() && pages.is_empty() && subsections.is_empty() {
+            return Err(format!("`{path}` is not a section").into());
+        }
+        Ok(serde_json::json!({
+            "path": self.relative(&dir),
+            "url": self.page_url(&dir.join("index.md")),
+            "title": index.as_ref().map(|index| index["title"].clone()),
+            "index": index,
+            "pages": pages,
+            "subsections": subsections,
+        }))
+    }
+
+    /// All pages below `section` except its index page. `filter` keeps pages with a truthy
+    /// `key` or with `key=value`, `sort_by` sorts by a field, descending with a leading `-`.
+    /// Keys may be nested, e.g. `extra.featured`.
+    pub fn pages(&self, section: &str, sort_by: Option<&str>, limit: Option<usize>, filter: Option<&str>) -> tera::Result<Vec<Value>> {
+        let dir = self.content_dir.join(section.trim_matches('/'));
+        let index = dir.join("index.md");
+        let mut pages = self
+            .sorted()
+            .into_iter()
+            .filter(|(file, _)| file.starts_with(&dir) && **file != index)
+            .map(|(file, page)| self.page_value(file, page))
+            .collect::<tera::Result<Vec<_>>>()?;
+
+        if let Some(filter) = filter {
+            pages.retain(|page| matches_filter(page, filter));
+        }
+        if let Some(sort_by) = sort_by {
+            let (key, descending) = match sort_by.strip_prefix('-') {
+                Some(key) => (key, true),
+                None => (sort_by, false),
+            };
+            // pages without the field go last in either direction
+            pages.sort_by(|a, b| match (field(a, key), field(b, key)) {
+                (Some(a), Some(b)) if descending => compare(b, a),
+                (Some(a), Some(b)) => compare(a, b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            });
+        }
+        if let Some(limit) = limit {
+            pages.truncate(limit);
+        }
+        Ok(pages)
+    }
+
+    fn sorted(&self) -> Vec<(&PathBuf, &Page)> {
+        let mut pages = self.library.iter().collect::<Vec<_>>();
+        pages.sort_by_key(|(file, _)| *file);
+        pages
+    }
+
+    fn page_value(&self, file: &Path, page: &Page) -> tera::Result<Value> {
+        let mut value = tera::to_value(page)?;
+        if let Value::Object(fields) = &mut value {
+            fields.insert("path".to_string(), Value::String(self.relative(file)));
+            fields.insert("url".to_string(), Value::String(self.page_url(file)));
+            fields.insert("content".to_string(), Value::String(page.content.clone()));
+        }
+        Ok(value)
+    }
+
+    fn page_url(&self, file: &Path) -> String {
+        let relative = file.strip_prefix(&self.content_dir).unwrap_or(file);
+        format!("{}{}", self.url_prefix, content_url(relative))
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.content_dir).unwrap_or(path).to_string_lossy().into_owned()
+    }
+}
+
+fn field<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.')
+        .try_fold(value, |value, key| value.get(key))
+        .filter(|value| !value.is_null())
+}
+
+fn matches_filter(page: &Value, filter: &str) -> bool {
+    let Some((key, expected)) = filter.split_once('=') else {
+        return field(page, filter.trim()).is_some_and(truthy);
+    };
+    // `draft=false` or `weight=10` compare as JSON values, everything else as a string
+    let expected = expected.trim();
+    let expected = serde_json::from_str(expected).unwrap_or_else(|_| Value::String(expected.to_string()));
+    let equals = |value: &Value| *value == expected;
+    match field(page, key.trim()) {
+        Some(Value::Array(values)) => values.iter().any(equals),
+        Some(value) => equals(value),
+        None => false,
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(value) => !value.is_empty(),
+        Value::Array(values) => !values.is_empty(),
+        Value::Object(fields) => !fields.is_empty(),
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        // dates are serialized as arrays of their components
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(a.len().cmp(&b.len())),
+        _ => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site() -> Site {
+        let pages = [
+            ("index.md", "# Home"),
+            ("blog/index.md", "---\ntitle: Blog\n---"),
+            ("blog/first.md", "---\ntitle: First\ncreated_at: 2025-01-10\nextra: {featured: true}\n---"),
+            ("blog/second.md", "---\ntitle: Second\ncreated_at: 2025-03-02\nauthors: [Alice]\n---"),
+            ("blog/2024/old.md", "---\ntitle: Old\ncreated_at: 2024-06-01\n---"),
+        ];
+        let library = pages
+            .into_iter()
+            .map(|(path, markdown)| (Path::new("content").join(path), Page::from_string(markdown).unwrap()))
+            .collect();
+        Site::new(Arc::new(library), PathBuf::from("content"), "/prefix".to_string())
+    }
+
+    fn titles(pages: &[Value]) -> Vec<&str> {
+        pages.iter().map(|page| page["title"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_get_page_and_url() {
+        let site = site();
+        let page = site.page("blog/first.md").unwrap();
+        assert_eq!(page["title"], "First");
+        assert_eq!(page["path"], "blog/first.md");
+        assert_eq!(page["url"], "/prefix/blog/first");
+        assert_eq!(site.url("/blog/index.md").unwrap(), "/prefix/blog/");
+        assert!(site.url("blog/missing.md").is_err());
+    }
+
+    #[test]
+    fn test_get_section() {
+        let section = site().section("blog").unwrap();
+        assert_eq!(section["title"], "Blog");
+        assert_eq!(section["url"], "/prefix/blog/");
+        assert_eq!(titles(section["pages"].as_array().unwrap()), ["First", "Second"]);
+        assert_eq!(section["subsections"], serde_json::json!(["blog/2024"]));
+        assert!(site().section("missing").is_err());
+    }
+
+    #[test]
+    fn test_get_pages() {
+        let site = site();
+        let latest = site.pages("blog", Some("-created_at"), Some(2), None).unwrap();
+        assert_eq!(titles(&latest), ["Second", "First"]);
+        assert_eq!(titles(&site.pages("", Some("title"), None, None).unwrap())[..2], ["Blog", "First"]);
+        assert_eq!(titles(&site.pages("blog", None, None, Some("extra.featured")).unwrap()), ["First"]);
+        assert_eq!(titles(&site.pages("blog", None, None, Some("authors=Alice")).unwrap()), ["Second"]);
+        assert_eq!(titles(&site.pages("blog", None, None, Some("extra.featured = true")).unwrap()), ["First"]);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{Error, Result, reload::Reloading};
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+const EXTENSIONS: [&str; 5] = ["yaml", "yml", "json", "toml", "csv"];
+
+/// Why a data file could not be loaded, the source of [`Error::Data`].
+#[derive(Debug)]
+pub enum DataError {
+    Yaml(serde_yaml::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    Csv(csv::Error),
+    UnsupportedFormat(String),
+    /// The file and a directory or another file map to the same key
+    Conflict(&'static str),
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::Yaml(err) => err.fmt(f),
+            DataError::Json(err) => err.fmt(f),
+            DataError::Toml(err) => err.fmt(f),
+            DataError::Csv(err) => err.fmt(f),
+            DataError::UnsupportedFormat(extension) => write!(f, "Unsupported data format `{extension}`"),
+            DataError::Conflict(message) => message.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+/// The data directory of a project, loaded once and in serve mode again when a file changed.
+#[derive(Default, Debug)]
+pub struct Data(Reloading<Value>);
+
+impl Data {
+    pub fn get(&self, dir: &Path) -> Result<Arc<Value>> {
+        self.0.get(&[dir.to_path_buf()], || load_dir(dir))
+    }
+}
+
+/// Files loaded by `load_data`, only parsed again if they were modified since the last call.
+#[derive(Default, Debug)]
+pub struct FileCache(Mutex<HashMap<PathBuf, (SystemTime, Value)>>);
+
+impl FileCache {
+    pub fn load(&self, path: &Path) -> Result<Value> {
+        let modified = fs::metadata(path).and_then(|meta| meta.modified()).map_err(Error::io(path))?;
+        if let Some((time, value)) = self.0.lock().unwrap().get(path)
+            && *time == modified
+        {
+            return Ok(value.clone());
+        }
+
+        let value = load_file(path)?;
+        self.0.lock().unwrap().insert(path.to_path_buf(), (modified, value.clone()));
+        Ok(value)
+    }
+}
+
+/// Loads all data files below `dir` into one object, nested by directory and keyed by file stem,
+/// e.g. `data/team/members.yaml` becomes `data.team.members`.
+pub fn load_dir(dir: &Path) -> Result<Value> {
+    let mut data = Map::new();
+    if !dir.is_dir() {
+        return Ok(Value::Object(data));
+    }
+
+    for entry in walkdir::WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry.map_err(|err| Error::io(dir)(err.into()))?;
+        let is_data = entry.path().extension().is_some_and(|ext| EXTENSIONS.iter().any(|known| ext == *known));
+        if !entry.file_type().is_file() || !is_data {
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        let mut object = &mut data;
+        for component in relative.parent().into_iter().flat_map(Path::components) {
+            let key = component.as_os_str().to_string_lossy().into_owned();
+            object = match object.entry(key).or_insert_with(|| Value::Object(Map::new())) {
+                Value::Object(object) => object,
+                _ => return Err(conflict(entry.path(), "its directory conflicts with a data file")),
+            };
+        }
+
+        let key = relative.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        if object.contains_key(&key) {
+            return Err(conflict(entry.path(), "it conflicts with another file or directory"));
+        }
+        object.insert(key, load_file(entry.path())?);
+    }
+
+    Ok(Value::Object(data))
+}
+
+/// Loads a single data file, the format is picked by its extension.
+pub fn load_file(path: &Path) -> Result<Value> {
+    let content = fs::read_to_string(path).map_err(Error::io(path))?;
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    parse(&content, &extension).map_err(|source| Error::Data {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn conflict(path: &Path, message: &'static str) -> Error {
+    Error::Data {
+        path: path.to_path_buf(),
+        source: DataError::Conflict(message),
+    }
+}
+
+fn parse(content: &str, extension: &str) -> Result<Value, DataError> {
+    match extension {
+        "yaml" | "yml" => serde_yaml::from_str(content).map_err(DataError::Yaml),
+        "json" => serde_json::from_str(content).map_err(DataError::Json),
+        "toml" => toml::from_str(content).map_err(DataError::Toml),
+        "csv" => parse_csv(content).map_err(DataError::Csv),
+        _ => Err(DataError::UnsupportedFormat(extension.to_string())),
+    }
+}
+
+/// Rows as objects keyed by the header row, all values are strings.
+fn parse_csv(content: &str) -> Result<Value, csv::Error> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    let mut rows = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let row = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(key, value)| (key.to_string(), Value::String(value.to_string())))
+            .collect::<Map<_, _>>();
+        rows.push(Value::Object(row));
+    }
+    Ok(Value::Array(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_formats() {
+        let expected = json!({ "name": "Ferrocyanide", "tags": ["ssg"] });
+        assert_eq!(parse("name: Ferrocyanide\ntags: [ssg]", "yaml").unwrap(), expected);
+        assert_eq!(parse(r#"{"name": "Ferrocyanide", "tags": ["ssg"]}"#, "json").unwrap(), expected);
+        assert_eq!(parse("name = \"Ferrocyanide\"\ntags = [\"ssg\"]", "toml").unwrap(), expected);
+        assert_eq!(
+            parse("version,date\n1.0,2025-01-01\n1.1,2025-02-01\n", "csv").unwrap(),
+            json!([{ "version": "1.0", "date": "2025-01-01" }, { "version": "1.1", "date": "2025-02-01" }])
+        );
+        assert!(matches!(parse("name: [", "yaml"), Err(DataError::Yaml(_))));
+        assert_eq!(parse("", "ini").unwrap_err().to_string(), "Unsupported data format `ini`");
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_path_buf();
+        fs::create_dir_all(dir.join("team")).unwrap();
+        fs::write(dir.join("releases.json"), "[1, 2]").unwrap();
+        fs::write(dir.join("team").join("members.yaml"), "- Alice\n- Bob").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let data = load_dir(&dir).unwrap();
+        assert_eq!(data, json!({ "releases": [1, 2], "team": { "members": ["Alice", "Bob"] } }));
+        assert_eq!(load_dir(&dir.join("missing")).unwrap(), json!({}));
+    }
+
+    #[test]
+    fn test_data_reload_on_change() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_path_buf();
+        fs::write(dir.join("site.yaml"), "name: first").unwrap();
+        let data = Data::default();
+
+        let first = data.get(&dir).unwrap();
+        assert!(Arc::ptr_eq(&first, &data.get(&dir).unwrap()));
+
+        fs::write(dir.join("site.yaml"), "name: second").unwrap();
+        std::thread::sleep(crate::reload::CHECK_INTERVAL);
+        assert_eq!(*data.get(&dir).unwrap(), json!({ "site": { "name": "second" } }));
+    }
+
+    #[test]
+    fn test_file_cache() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("releases.json");
+        fs::write(&path, "[1]").unwrap();
+        let files = FileCache::default();
+        assert_eq!(files.load(&path).unwrap(), json!([1]));
+        assert!(files.load(&temp.path().join("missing.json")).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{
+    content::{frontmatter::FrontmatterError, markdown::MarkdownError},
+    data::DataError,
+};
+use std::{error::Error as StdError, fmt, io, path::PathBuf};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+type Source = Box<dyn StdError + Send + Sync>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// `config.yaml` or a directory config is invalid
+    Config {
+        path: PathBuf,
+        source: Source,
+    },
+    Frontmatter(FrontmatterError),
+    /// Markdown could not be rendered, e.g. because of invalid math, links or shortcodes
+    Markdown {
+        path: Option<PathBuf>,
+        source: MarkdownError,
+    },
+    /// Templates failed to compile, `template` and `page` are set if rendering a page failed
+    Template {
+        template: Option<String>,
+        page: Option<PathBuf>,
+        source: tera::Error,
+    },
+    /// A data file could not be loaded
+    Data {
+        path: PathBuf,
+        source: DataError,
+    },
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// The page is not part of the library, e.g. because it was created after startup
+    MissingPage(PathBuf),
+}
+
+impl Error {
+    /// Maps an `io::Error` to [`Error::Io`] for `path`.
+    pub(crate) fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+        move |source| Error::Io { path: Some(path), source }
+    }
+
+    /// The error and all of its sources, outermost first.
+    pub fn chain(&self) -> impl Iterator<Item = &(dyn StdError + 'static)> {
+        let mut next = Some(self as &(dyn StdError + 'static));
+        std::iter::from_fn(move || {
+            let err = next?;
+            next = err.source();
+            Some(err)
+        })
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config { path, source } => {
+                write!(f, "Invalid config {}", path.display())?;
+                // `path:line:column` so terminals and editors can jump to the error
+                match source.downcast_ref::<serde_yaml::Error>().and_then(serde_yaml::Error::location) {
+                    Some(location) => write!(f, ":{}:{}", location.line(), location.column()),
+                    None => Ok(()),
+                }
+            }
+            Error::Frontmatter(err) => err.fmt(f),
+            Error::Markdown { path: Some(path), .. } => write!(f, "Failed to render markdown of {}", path.display()),
+            Error::Markdown { path: None, .. } => write!(f, "Failed to render markdown"),
+            Error::Template {
+                template: Some(template),
+                page: Some(page),
+                ..
+            } => write!(f, "Failed to render {} with template `{template}`", page.display()),
+            Error::Template {
+                template: Some(template), ..
+            } => write!(f, "Failed to render template `{template}`"),
+            Error::Template { .. } => write!(f, "Failed to compile templates"),
+            Error::Data { path, .. } => write!(f, "Failed to load data file {}", path.display()),
+            Error::Io { path: Some(path), .. } => write!(f, "Failed to access {}", path.display()),
+            Error::Io { path: None, source } => source.fmt(f),
+            Error::MissingPage(path) => write!(f, "{} is not part of the site, restart to pick up new pages", path.display()),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Config { source, .. } => Some(source.as_ref()),
+            Error::Markdown { source, .. } => Some(source),
+            Error::Data { source, .. } => Some(source),
+            Error::Template { source, .. } => Some(source),
+            Error::Io { path: Some(_), source } => Some(source),
+            Error::Io { path: None, source } => source.source(),
+            Error::Frontmatter(_) | Error::MissingPage(_) => None,
+        }
+    }
+}
+
+impl From<FrontmatterError> for Error {
+    fn from(err: FrontmatterError) -> Self {
+        Error::Frontmatter(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io { path: None, source }
+    }
+}
+
+impl From<tera::Error> for Error {
+    fn from(source: tera::Error) -> Self {
+        Error::Template {
+            template: None,
+            page: None,
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain() {
+        let err = Error::io("content/index.md")(io::Error::new(io::ErrorKind::NotFound, "not found"));
+        let chain = err.chain().map(|err| err.to_string()).collect::<Vec<_>>();
+        assert_eq!(chain, ["Failed to access content/index.md", "not found"]);
+
+        let err = Error::MissingPage(PathBuf::from("content/new.md"));
+        assert_eq!(err.chain().count(), 1);
+        assert!(matches!(Error::from(tera::Error::msg("invalid")), Error::Template { template: None, .. }));
+    }
+}
@@ -0,0 +1,316 @@
+use crate::{
+    config::MarkdownConfig,
+    content::{frontmatter::parse_datetime, markdown, stable_hash},
+};
+use std::{collections::HashMap, fs, path::PathBuf};
+use tera::Value;
+use time::{Date, PrimitiveDateTime, Time, format_description};
+
+const DEFAULT_DATE_FORMAT: &str = "[year]-[month]-[day]";
+const DEFAULT_TRUNCATE_LENGTH: usize = 255;
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+/// Filter whose output is not escaped, for filters producing HTML or script content.
+pub struct Safe<F>(pub F);
+
+impl<F> tera::Filter for Safe<F>
+where
+    F: Fn(&Value, &HashMap<String, Value>) -> tera::Result<Value> + Send + Sync,
+{
+    fn filter(&self, value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        (self.0)(value, args)
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Renders a markdown string, `inline=true` drops the paragraph around a single line.
+pub fn markdown(value: &Value, args: &HashMap<String, Value>, config: &MarkdownConfig) -> tera::Result<Value> {
+    let content = value.as_str().ok_or("Expected a string for markdown")?;
+    let html = markdown::render_snippet(content, config).map_err(|err| tera::Error::chain("Failed to render markdown", err))?;
+    let inline = args.get("inline").and_then(Value::as_bool).unwrap_or(false);
+    let paragraph = html.trim_end().strip_prefix("<p>").and_then(|html| html.strip_suffix("</p>"));
+    Ok(Value::String(match paragraph {
+        Some(paragraph) if inline && !paragraph.contains("<p>") => paragraph.to_string(),
+        _ => html,
+    }))
+}
+
+/// Formats a frontmatter date with a `time` format description, e.g. `format="[day].[month].[year]"`.
+pub fn date_format(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let datetime = match value {
+        Value::String(date) => parse_datetime(date),
+        Value::Array(components) => from_components(components),
+        _ => None,
+    }
+    .ok_or_else(|| format!("Expected a date for date_format, got `{value}`"))?;
+
+    let format = args.get("format").and_then(Value::as_str).unwrap_or(DEFAULT_DATE_FORMAT);
+    let description = format_description::parse(format).map_err(|err| tera::Error::chain(format!("Invalid date format `{format}`"), err))?;
+    let formatted = datetime
+        .format(&description)
+        .map_err(|err| tera::Error::chain(format!("Failed to format date with `{format}`"), err))?;
+    Ok(Value::String(formatted))
+}
+
+/// Dates are serialized as `[year, ordinal, hour, minute, second, nanosecond]`.
+fn from_components(components: &[Value]) -> Option<PrimitiveDateTime> {
+    let [year, ordinal, hour, minute, second, nanosecond] = components else {
+        return None;
+    };
+    let date = Date::from_ordinal_date(year.as_i64()? as i32, ordinal.as_u64()? as u16).ok()?;
+    let time = Time::from_hms_nano(
+        hour.as_u64()? as u8,
+        minute.as_u64()? as u8,
+        second.as_u64()? as u8,
+        nanosecond.as_u64()? as u32,
+    )
+    .ok()?;
+    Some(PrimitiveDateTime::new(date, time))
+}
+
+pub fn slugify(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let text = value.as_str().ok_or("Expected a string for slugify")?;
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    Ok(Value::String(slug.trim_end_matches('-').to_string()))
+}
+
+/// Reading time in minutes of a text or HTML string, or of a word count.
+pub fn reading_time(value: &Value, config: &MarkdownConfig) -> tera::Result<Value> {
+    let words = match value {
+        Value::Number(words) => words.as_f64().unwrap_or_default(),
+        Value::String(text) => strip_tags(text).split_whitespace().count() as f64,
+        _ => return Err("Expected a string or a word count for reading_time".into()),
+    };
+    Ok(Value::from(markdown::reading_time(words, config)))
+}
+
+/// Site path with the `rootDir` prefix and the `baseUrl` of the project, absolute URLs are kept.
+pub fn absolute_url(value: &Value, base_url: Option<&str>, prefix: &str) -> tera::Result<Value> {
+    let path = value.as_str().ok_or("Expected a string for absolute_url")?;
+    if path.contains("://") {
+        return Ok(value.clone());
+    }
+    let base_url = base_url.unwrap_or_default().trim_end_matches('/');
+    Ok(Value::String(format!("{base_url}{prefix}/{}", path.trim_start_matches('/'))))
+}
+
+/// URL of a file in the first of `asset_dirs` containing it, with a content hash so browsers
+/// fetch it again after changes.
+pub fn asset(value: &Value, asset_dirs: &[PathBuf], prefix: &str) -> tera::Result<Value> {
+    let path = value.as_str().ok_or("Expected a string for asset")?.trim_start_matches('/');
+    let file = asset_dirs
+        .iter()
+        .map(|dir| dir.join(path))
+        .find(|file| file.is_file())
+        .ok_or_else(|| format!("Asset `{path}` does not exist"))?;
+    let content = fs::read(&file).map_err(|err| tera::Error::chain(format!("Failed to read asset `{path}`"), err))?;
+    Ok(Value::String(format!("{prefix}/assets/{path}?v={:08x}", stable_hash(&content) as u32)))
+}
+
+/// Truncates HTML to `length` characters of text and closes all open elements.
+pub fn truncate_html(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let html = value.as_str().ok_or("Expected a string for truncate_html")?;
+    let length = match args.get("length") {
+        Some(length) => length.as_u64().ok_or("Expected a number for length")? as usize,
+        None => DEFAULT_TRUNCATE_LENGTH,
+    };
+    let end = args.get("end").and_then(Value::as_str).unwrap_or("…");
+
+    let mut truncated = String::with_capacity(html.len().min(length * 2));
+    let mut open = vec![];
+    let mut count = 0;
+    let mut rest = html;
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            let tag = &rest[..rest.find('>').map_or(rest.len(), |end| end + 1)];
+            let name = tag
+                .trim_start_matches(['<', '/'])
+                .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            if tag.starts_with("</") {
+                if let Some(index) = open.iter().rposition(|open| *open == name) {
+                    open.truncate(index);
+                }
+            } else if !tag.starts_with("<!") && !tag.ends_with("/>") && !VOID_ELEMENTS.contains(&name.as_str()) {
+                open.push(name);
+            }
+            truncated.push_str(tag);
+            rest = &rest[tag.len()..];
+            continue;
+        }
+
+        if count == length {
+            truncated.truncate(truncated.trim_end().len());
+            truncated.push_str(end);
+            for name in open.iter().rev() {
+                truncated.push_str(&format!("</{name}>"));
+            }
+            return Ok(Value::String(truncated));
+        }
+        // an entity like `&amp;` is a single character
+        let len = match rest.find(';') {
+            Some(end) if c == '&' && end < 10 && !rest[..end].contains(char::is_whitespace) => end + 1,
+            _ => c.len_utf8(),
+        };
+        truncated.push_str(&rest[..len]);
+        rest = &rest[len..];
+        count += 1;
+    }
+    Ok(value.clone())
+}
+
+/// Serializes a value for a `<script type="application/ld+json">` element, objects default to
+/// the schema.org context.
+pub fn json_ld(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let mut value = value.clone();
+    if let Value::Object(fields) = &mut value
+        && !fields.contains_key("@context")
+    {
+        fields.insert("@context".to_string(), Value::String("https://schema.org".to_string()));
+    }
+    let json = serde_json::to_string(&value).map_err(tera::Error::json)?;
+    // the script element must not be closed by a string in the data
+    Ok(Value::String(
+        json.replace('<', "\\u003c").replace('>', "\\u003e").replace('&', "\\u0026"),
+    ))
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn no_args() -> HashMap<String, Value> {
+        HashMap::new()
+    }
+
+    #[test]
+    fn test_markdown() {
+        let config = MarkdownConfig::default();
+        let html = markdown(&json!("Some *emphasis*"), &no_args(), &config).unwrap();
+        assert_eq!(html, "<p>Some <em>emphasis</em></p>\n");
+        let inline = markdown(&json!("Some *emphasis*"), &args(json!({ "inline": true })), &config).unwrap();
+        assert_eq!(inline, "Some <em>emphasis</em>");
+        let blocks = markdown(&json!("One\n\nTwo"), &args(json!({ "inline": true })), &config).unwrap();
+        assert_eq!(blocks, "<p>One</p>\n<p>Two</p>\n");
+    }
+
+    #[test]
+    fn test_date_format() {
+        let serialized = serde_json::to_value(time::macros::datetime!(2025-04-30 13:05)).unwrap();
+        assert_eq!(date_format(&serialized, &no_args()).unwrap(), "2025-04-30");
+        let format = args(json!({ "format": "[day].[month].[year] [hour]:[minute]" }));
+        assert_eq!(date_format(&serialized, &format).unwrap(), "30.04.2025 13:05");
+        assert_eq!(date_format(&json!("2025-01-02"), &no_args()).unwrap(), "2025-01-02");
+        assert!(date_format(&Value::Null, &no_args()).is_err());
+        assert!(date_format(&json!("2025-01-02"), &args(json!({ "format": "[nope]" }))).is_err());
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify(&json!("Hello, World!"), &no_args()).unwrap(), "hello-world");
+        assert_eq!(slugify(&json!("  Ärger im   Büro 2025 "), &no_args()).unwrap(), "ärger-im-büro-2025");
+    }
+
+    #[test]
+    fn test_reading_time() {
+        let config = MarkdownConfig {
+            words_per_minute: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(reading_time(&json!("<p>one two</p><p>three</p>"), &config).unwrap(), 2);
+        assert_eq!(reading_time(&json!(400), &MarkdownConfig::default()).unwrap(), 2);
+        assert!(reading_time(&Value::Null, &config).is_err());
+    }
+
+    #[test]
+    fn test_absolute_url() {
+        let url = |path| absolute_url(&json!(path), Some("https://example.com/"), "/docs").unwrap();
+        assert_eq!(url("/guide/"), "https://example.com/docs/guide/");
+        assert_eq!(url("guide"), "https://example.com/docs/guide");
+        assert_eq!(url("https://other.org/"), "https://other.org/");
+        assert_eq!(absolute_url(&json!("/guide"), None, "").unwrap(), "/guide");
+    }
+
+    #[test]
+    fn test_asset() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_path_buf();
+        let dirs = [dir.join("assets"), dir.join("theme")];
+        fs::create_dir_all(&dirs[0]).unwrap();
+        fs::create_dir_all(&dirs[1]).unwrap();
+        fs::write(dirs[0].join("style.css"), "body {}").unwrap();
+        fs::write(dirs[1].join("theme.css"), "html {}").unwrap();
+
+        let first = asset(&json!("/style.css"), &dirs, "/docs").unwrap();
+        let first = first.as_str().unwrap();
+        assert!(first.starts_with("/docs/assets/style.css?v="));
+        fs::write(dirs[0].join("style.css"), "body { margin: 0 }").unwrap();
+        assert_ne!(asset(&json!("style.css"), &dirs, "/docs").unwrap(), first);
+        assert!(
+            asset(&json!("theme.css"), &dirs, "")
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .starts_with("/assets/theme.css?v=")
+        );
+        assert!(asset(&json!("missing.css"), &dirs, "").is_err());
+    }
+
+    #[test]
+    fn test_truncate_html() {
+        let html = json!("<p>Hello <strong>brave</strong> new <em>world</em></p><p>More</p>");
+        let truncate = |length: usize| truncate_html(&html, &args(json!({ "length": length }))).unwrap();
+        assert_eq!(truncate(9), "<p>Hello <strong>bra…</strong></p>");
+        assert_eq!(truncate(5), "<p>Hello…</p>");
+        assert_eq!(truncate(100), html);
+        let entities = truncate_html(&json!("a &amp; b<br>c"), &args(json!({ "length": 3, "end": "..." }))).unwrap();
+        assert_eq!(entities, "a &amp;...");
+    }
+
+    #[test]
+    fn test_json_ld() {
+        let value = json!({ "@type": "Article", "headline": "</script><b>" });
+        let json = json_ld(&value, &no_args()).unwrap();
+        let json = json.as_str().unwrap();
+        assert!(!json.contains("</script>"));
+        let parsed: Value = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed["@context"], "https://schema.org");
+        assert_eq!(parsed["headline"], "</script><b>");
+    }
+}
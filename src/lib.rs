@@ -1,20 +1,28 @@
 use axum::http::{StatusCode, Uri};
-use config::{AppConfig, ProjectConfig, get_config_path};
-use content::page::{NavItem, filename_to_url};
+use config::{AppConfig, ProjectConfig, get_config_path, read_project_config, resolve_nav};
+use content::{
+    cascade,
+    markdown::RenderContext,
+    page::{NavItem, Page, content_url, limit_outline},
+};
+pub use error::{Error, Result};
 use once_cell::sync::Lazy;
-use serde_yaml::Value;
+use serde_yaml::{Mapping, Value};
 use std::{
-    collections::HashMap,
-    fs,
     path::{Path, PathBuf},
-    sync::RwLock,
+    sync::{Arc, RwLock},
 };
-use templates::{TEMPLATES, load_templates};
+use templates::TEMPLATES;
+use tera::Tera;
 
 pub mod build;
 pub mod cli;
 pub mod config;
 pub mod content;
+pub mod data;
+pub mod error;
+pub mod filters;
+pub mod reload;
 pub mod server;
 pub mod templates;
 
@@ -26,86 +34,189 @@ enum Context {
 
 static CONTEXT: Lazy<RwLock<Context>> = Lazy::new(|| RwLock::new(Context::Serve));
 
-pub fn render(markdown: impl AsRef<Path>, config: &AppConfig) -> anyhow::Result<String> {
+pub fn render(markdown: impl AsRef<Path>, config: &AppConfig) -> Result<String> {
     render_page(markdown, config)
 }
 
-pub fn render_page(filename: impl AsRef<Path>, config: &AppConfig) -> anyhow::Result<String> {
+pub fn render_page(filename: impl AsRef<Path>, config: &AppConfig) -> Result<String> {
     let parent_dir = filename.as_ref().parent().unwrap_or(&config.folder);
-    let mut template = String::from("layout.html");
 
-    let tera = load_templates(config)?;
+    let tera = config.tera()?;
     let mut context = tera::Context::new();
     context.insert("config", &config.project_config);
+    context.insert("data", &*config.data()?);
 
-    let dir_config = parent_dir.join(format!("{}.yaml", parent_dir.file_name().unwrap().to_string_lossy()));
-    if dir_config.exists() {
-        let dir_config = fs::read_to_string(&dir_config)?;
-        let yml = serde_yaml::from_str::<HashMap<String, Value>>(&dir_config)?;
-        for (key, value) in yml {
-            if key == "layout" {
-                template = if let Value::String(value) = &value {
-                    value.to_string()
-                } else {
-                    template
-                };
-            }
-            context.insert(&key, &value);
+    let Some(page) = config.library.get(filename.as_ref()) else {
+        return Err(Error::MissingPage(filename.as_ref().to_path_buf()));
+    };
+    let content_dir = config.folder.join("content");
+    let mut settings = cascade::dir_config(&content_dir, parent_dir)?;
+    let template = resolve_layout(&tera, filename.as_ref(), page.frontmatter.layout.as_deref(), &settings)?;
+    cascade::merge(&mut settings, page.frontmatter.overrides());
+    for (key, value) in &settings {
+        if let Some(key) = key.as_str() {
+            context.insert(key, value);
         }
     }
+    let setting = |key: &str| settings.get(key).and_then(Value::as_u64).map(|value| value as u8);
 
     // get library tree
-    let nav = config
-        .library
-        .iter()
-        .filter(|(key, _)| key.starts_with(parent_dir.to_string_lossy().into_owned()))
+    let mut pages = config.library.iter().filter(|(key, _)| key.starts_with(parent_dir)).collect::<Vec<_>>();
+    pages.sort_by_key(|(key, _)| *key);
+    let nav = pages
+        .into_iter()
         .map(|(key, value)| NavItem {
-            url: filename_to_url(key, config),
-            title: value.frontmatter.title.clone().unwrap(),
+            url: content_url(key.strip_prefix(&content_dir).unwrap_or(key)),
+            title: value.frontmatter.title.clone().unwrap_or_default(),
         })
         .collect::<Vec<_>>();
     context.insert("sitenav", &nav);
     context.insert("path", &resolve_path(filename.as_ref(), &config.folder));
 
-    let page = &config.library[&filename.as_ref().to_path_buf()];
+    let page = &Page {
+        outline: limit_outline(&page.outline, setting("toc_min_level").unwrap_or(1), setting("toc_depth")),
+        ..page.clone()
+    };
     context.insert("page", page);
     context.insert("content", &page.content);
 
-    let rendered = tera.render(&template, &context).unwrap();
+    let rendered = tera.render(&template, &context).map_err(|source| Error::Template {
+        template: Some(template),
+        page: Some(filename.as_ref().to_path_buf()),
+        source,
+    })?;
 
     Ok(rendered)
 }
 
+/// Developer error page for `serve`, listing the page, the template and the full error chain.
+pub fn render_error_overlay(filename: &Path, err: &Error) -> String {
+    let errors = err.chain().map(|cause| cause.to_string()).collect::<Vec<_>>();
+    let mut context = tera::Context::new();
+    context.insert("page", &filename.display().to_string());
+    let template = match err {
+        Error::Template { template, .. } => template.clone(),
+        _ => None,
+    };
+    context.insert("template", &template.or_else(|| errors.iter().find_map(|error| parsed_template(error))));
+    context.insert("line", &errors.iter().find_map(|error| template_line(error)));
+    context.insert("errors", &errors);
+    TEMPLATES
+        .render("__builtins/error-overlay.html", &context)
+        .unwrap_or_else(|_| format!("<pre>{err:?}</pre>"))
+}
+
+/// Template file named by Tera parse errors.
+fn parsed_template(error: &str) -> Option<String> {
+    let (_, rest) = error.split_once("Failed to parse \"")?;
+    Some(rest.split_once('"')?.0.to_string())
+}
+
+/// Line from the ` --> line:column` marker of Tera parse errors.
+fn template_line(error: &str) -> Option<usize> {
+    let (_, location) = error.split_once("--> ")?;
+    location.split(':').next()?.trim().parse().ok()
+}
+
+/// Template of a page: the `layout` of its frontmatter, then of the directory cascade, then
+/// `section.html` of the project or its theme for index pages, then `layout.html` and finally
+/// `section.html` or `page.html` of the default theme.
+fn resolve_layout(tera: &Tera, filename: &Path, layout: Option<&str>, settings: &Mapping) -> Result<String> {
+    let exists = |template: &str| tera.get_template_names().any(|name| name == template);
+    let from_file = |name: &str| tera.templates.get(name).is_some_and(|template| template.path.is_some());
+    let is_section = filename.file_stem().is_some_and(|stem| stem == "index");
+    let template = layout
+        .or_else(|| settings.get("layout").and_then(Value::as_str))
+        .map(str::to_string)
+        .or_else(|| (is_section && from_file("section.html")).then(|| "section.html".to_string()))
+        .or_else(|| exists("layout.html").then(|| "layout.html".to_string()))
+        .unwrap_or_else(|| if is_section { "section.html" } else { "page.html" }.to_string());
+    if !exists(&template) {
+        return Err(Error::Template {
+            source: tera::Error::template_not_found(&template),
+            template: Some(template),
+            page: Some(filename.to_path_buf()),
+        });
+    }
+    Ok(template)
+}
+
 pub fn render_error(config: &AppConfig, code: StatusCode) -> Option<String> {
     let mut context = tera::Context::new();
     context.insert("statuscode", &code.as_u16());
     context.insert("message", &code.canonical_reason());
-    if let Ok(tera) = load_templates(config) {
-        if let Ok(html) = tera.render("error.html", &context) {
-            return Some(html);
+    context.insert("config", &config.project_config);
+    if let Ok(data) = config.data() {
+        context.insert("data", &*data);
+    }
+    if let Ok(tera) = config.tera() {
+        // templates of the project or its theme first, then the default theme
+        let code_template = format!("{}.html", code.as_u16());
+        let from_file = |name: &str| tera.templates.get(name).is_some_and(|template| template.path.is_some());
+        let candidates = [code_template.as_str(), "error.html"]
+            .into_iter()
+            .filter(|name| from_file(name))
+            .chain([code_template.as_str(), "__builtins/error.html"]);
+        for name in candidates {
+            if let Ok(html) = tera.render(name, &context) {
+                return Some(html);
+            }
         }
-        return tera.render("__builtins/error.html", &context).ok();
+        return None;
     }
     TEMPLATES.render("__builtins/error.html", &context).ok()
 }
 
-fn read_config(folder: Option<PathBuf>) -> anyhow::Result<AppConfig> {
+pub fn read_config(folder: Option<PathBuf>, env: Option<&str>) -> Result<AppConfig> {
     let folder = folder.unwrap_or(PathBuf::from("."));
-    let config_file = get_config_path(&folder);
+    let project_config = read_project_config(&folder, env)?;
+    let mut config = AppConfig {
+        folder,
+        project_config,
+        library: Default::default(),
+        templates: Default::default(),
+        data: Default::default(),
+    };
+    // fail early on invalid data files, build renders reuse them and serve loads them again on changes
+    let data = config.data()?;
 
-    let project_config = if config_file.exists() {
-        let config_file = fs::read_to_string(&config_file)?;
-        serde_yaml::from_str::<ProjectConfig>(&config_file).unwrap()
-    } else {
-        ProjectConfig::default()
+    let tera = config.tera()?;
+    let ctx = RenderContext {
+        tera: Some(&tera),
+        data: Some(&data),
+        config: config.project_config.markdown.clone(),
+        cache_dir: Some(config.folder.join(".cache").join("diagrams")),
+        content_dir: Some(config.folder.join("content")),
+        url_prefix: url_prefix(&config.project_config),
+        ..Default::default()
     };
-    let library = content::read_files(&folder)?;
+    let mut library = content::read_files(&config.folder.join("content"), &ctx)?;
 
-    Ok(AppConfig {
-        folder,
-        project_config,
-        library,
-    })
+    if config.project_config.git_metadata {
+        let history = content::git::read_history(&config.folder)?;
+        for (path, page) in library.iter_mut() {
+            if let Some(info) = history.get(path) {
+                info.apply(&mut page.frontmatter);
+            }
+        }
+    }
+    resolve_nav(&mut config.project_config.nav, &config.folder.join("content"), &library).map_err(|err| Error::Config {
+        path: get_config_path(&config.folder),
+        source: err.into(),
+    })?;
+
+    // the site functions of the page templates need the library, compile them again
+    config.library = Arc::new(library);
+    config.templates = Default::default();
+    Ok(config)
+}
+
+/// Prefix for site-absolute URLs, only applied in build mode.
+fn url_prefix(project_config: &ProjectConfig) -> String {
+    if *CONTEXT.read().unwrap() != Context::Build {
+        return String::new();
+    }
+    project_config.project.as_ref().and_then(|meta| meta.root_dir.clone()).unwrap_or_default()
 }
 
 fn resolve_filename(uri: &Uri, root_dir: &Path) -> PathBuf {
@@ -132,3 +243,82 @@ fn resolve_path(path: &Path, root_dir: &Path) -> String {
     let as_str = as_str.strip_suffix("index").unwrap_or(as_str);
     format!("/{}", as_str.strip_suffix("/").unwrap_or(as_str))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_overlay() {
+        let mut tera = Tera::default();
+        tera.add_raw_template("page.html", "{{ missing }}").unwrap();
+        let err = Error::Template {
+            template: Some("page.html".to_string()),
+            page: None,
+            source: tera.render("page.html", &tera::Context::new()).unwrap_err(),
+        };
+
+        let html = render_error_overlay(Path::new("content/index.md"), &err);
+        assert!(html.contains("<h1>Failed to render content&#x2F;index.md</h1>"));
+        assert!(html.contains("<dd>page.html</dd>"));
+        assert!(html.contains("Variable `missing` not found in context"));
+    }
+
+    #[test]
+    fn test_template_line() {
+        assert_eq!(template_line(" --> 3:7\n  |\n3 | {{ page.title }"), Some(3));
+        assert_eq!(template_line("Failed to render 'page.html'"), None);
+        assert_eq!(
+            parsed_template("\n* Failed to parse \"templates/base.html\"\n  --> 3:7"),
+            Some("templates/base.html".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_layout() {
+        let mut tera = Tera::default();
+        for name in ["layout.html", "doc.html", "post.html"] {
+            tera.add_raw_template(name, "").unwrap();
+        }
+        let page = Path::new("content/docs/guide/intro.md");
+        let cascade = serde_yaml::from_str::<Mapping>("layout: doc.html").unwrap();
+        let empty = Mapping::new();
+        let resolve = |filename: &Path, layout, settings: &Mapping| resolve_layout(&tera, filename, layout, settings).unwrap();
+
+        assert_eq!(resolve(page, Some("post.html"), &cascade), "post.html");
+        assert_eq!(resolve(page, None, &cascade), "doc.html");
+        assert_eq!(resolve(page, None, &empty), "layout.html");
+        assert_eq!(resolve(Path::new("content/docs/index.md"), None, &empty), "layout.html");
+
+        // a section template of the project wins over `layout.html` for index pages
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join("section.html"), "").unwrap();
+        let mut sections = tera.clone();
+        sections
+            .add_template_file(temp.path().join("section.html"), Some("section.html"))
+            .unwrap();
+        let resolve = |filename| resolve_layout(&sections, Path::new(filename), None, &empty).unwrap();
+        assert_eq!(resolve("content/docs/index.md"), "section.html");
+        assert_eq!(resolve("content/docs/intro.md"), "layout.html");
+        assert_eq!(
+            resolve_layout(&sections, Path::new("content/docs/index.md"), None, &cascade).unwrap(),
+            "doc.html"
+        );
+
+        let defaults = Tera::default();
+        let resolve = |filename| resolve_layout(&defaults, Path::new(filename), None, &empty);
+        assert!(resolve("content/blog/post.md").is_err());
+        let mut defaults = Tera::default();
+        defaults.extend(&templates::DEFAULT_THEME).unwrap();
+        let resolve = |filename| resolve_layout(&defaults, Path::new(filename), None, &empty).unwrap();
+        assert_eq!(resolve("content/blog/post.md"), "page.html");
+        assert_eq!(resolve("content/blog/index.md"), "section.html");
+
+        let err = resolve_layout(&tera, page, Some("missing.html"), &cascade).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to render content/docs/guide/intro.md with template `missing.html`"
+        );
+        assert_eq!(err.chain().nth(1).unwrap().to_string(), "Template 'missing.html' not found");
+    }
+}
@@ -1,15 +1,25 @@
 use clap::Parser;
 use ferrocyanide::{
     build,
-    cli::{Cli, Commands},
-    server,
+    cli::{Cli, Commands, ConfigCommands},
+    config, server,
 };
+use std::path::Path;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     match cli.command {
-        Commands::Serve { folder } => server::serve(folder).await,
-        Commands::Build { folder } => build::build(folder),
+        Commands::Serve { folder } => server::serve(folder, cli.env.as_deref()).await?,
+        Commands::Build { folder } => build::build(folder, Path::new("dist"), cli.env.as_deref())?,
+        Commands::Config {
+            command: Some(ConfigCommands::Schema),
+            ..
+        } => println!("{}", serde_json::to_string_pretty(&config::schema())?),
+        Commands::Config { folder, command: None } => {
+            tracing_subscriber::fmt().with_target(false).with_writer(std::io::stderr).init();
+            print!("{}", config::dump_config(&folder.unwrap_or(".".into()), cli.env.as_deref())?)
+        }
     }
+    Ok(())
 }
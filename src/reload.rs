@@ -0,0 +1,78 @@
+use crate::{CONTEXT, Context, Result};
+use std::{
+    fmt, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+/// Minimum time between two checks for changed files in serve mode.
+pub const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Value loaded from the files of some directories. In serve mode it is loaded again when one of
+/// the files changed, which is checked at most every [`CHECK_INTERVAL`], otherwise only once.
+pub struct Reloading<T> {
+    state: RwLock<Option<State<T>>>,
+}
+
+struct State<T> {
+    stamp: u64,
+    checked: Instant,
+    value: Arc<T>,
+}
+
+impl<T> Default for Reloading<T> {
+    fn default() -> Self {
+        Reloading { state: RwLock::new(None) }
+    }
+}
+
+impl<T> fmt::Debug for Reloading<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let loaded = self.state.read().unwrap().is_some();
+        f.debug_struct("Reloading").field("loaded", &loaded).finish()
+    }
+}
+
+impl<T> Reloading<T> {
+    pub fn get(&self, dirs: &[PathBuf], load: impl FnOnce() -> Result<T>) -> Result<Arc<T>> {
+        let serve = *CONTEXT.read().unwrap() == Context::Serve;
+        if let Some(state) = &*self.state.read().unwrap()
+            && (!serve || state.checked.elapsed() < CHECK_INTERVAL)
+        {
+            return Ok(state.value.clone());
+        }
+
+        let stamp = if serve { fingerprint(dirs) } else { 0 };
+        let mut state = self.state.write().unwrap();
+        if let Some(state) = &mut *state
+            && state.stamp == stamp
+        {
+            state.checked = Instant::now();
+            return Ok(state.value.clone());
+        }
+
+        let value = Arc::new(load()?);
+        *state = Some(State {
+            stamp,
+            checked: Instant::now(),
+            value: value.clone(),
+        });
+        Ok(value)
+    }
+}
+
+/// Hash of the paths, sizes and modification times of all files in `dirs`.
+fn fingerprint(dirs: &[PathBuf]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let entries = dirs.iter().flat_map(|dir| walkdir::WalkDir::new(dir).sort_by_file_name());
+    for entry in entries.flatten() {
+        entry.path().hash(&mut hasher);
+        if let Ok(meta) = fs::metadata(entry.path()) {
+            meta.len().hash(&mut hasher);
+            meta.modified().ok().hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
@@ -1,12 +1,12 @@
 pub(crate) mod router;
-use crate::{read_config, Context, CONTEXT};
+use crate::{read_config, Context, Result, CONTEXT};
 use router::app;
 use std::path::PathBuf;
 use tokio::net::TcpListener;
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-pub async fn serve(folder: Option<PathBuf>) -> anyhow::Result<()> {
+pub async fn serve(folder: Option<PathBuf>, env: Option<&str>) -> Result<()> {
     *CONTEXT.write().unwrap() = Context::Serve;
     tracing_subscriber::registry()
         .with(
@@ -17,7 +17,7 @@ pub async fn serve(folder: Option<PathBuf>) -> anyhow::Result<()> {
         .init();
 
     let listener = TcpListener::bind("0.0.0.0:8192").await?;
-    let config = read_config(folder)?;
+    let config = read_config(folder, env)?;
 
     info!("Listening on http://{}", listener.local_addr()?);
     axum::serve(listener, app(config)).await?;
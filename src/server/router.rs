@@ -1,4 +1,4 @@
-use crate::{config::AppConfig, content::COLOR_PICKER_JS, render, render_error, resolve_filename};
+use crate::{config::AppConfig, content::COLOR_PICKER_JS, render, render_error, render_error_overlay, resolve_filename};
 use axum::{
     Router,
     extract::{Request, State},
@@ -8,11 +8,12 @@ use axum::{
     routing::get,
 };
 use tower_http::{compression::CompressionLayer, decompression::RequestDecompressionLayer, services::ServeDir, trace::TraceLayer};
+use tracing::error;
 
 pub(crate) fn app(config: AppConfig) -> Router {
     axum::Router::new()
         .route("/js/SwitchColorMode.js", get(color_picker))
-        .nest_service("/assets", ServeDir::new(config.folder.join("assets")))
+        .nest_service("/assets", assets(&config))
         .fallback(handler)
         .with_state(config)
         .layer(TraceLayer::new_for_http().on_failure(()))
@@ -21,12 +22,21 @@ pub(crate) fn app(config: AppConfig) -> Router {
         .layer(middleware::from_fn(redirect_index))
 }
 
+/// Project assets, falling back to the assets of the theme.
+fn assets(config: &AppConfig) -> ServeDir<ServeDir> {
+    let dirs = config.asset_dirs();
+    ServeDir::new(&dirs[0]).fallback(ServeDir::new(dirs.last().unwrap_or(&dirs[0])))
+}
+
 pub(crate) async fn handler(ctx: State<AppConfig>, uri: Uri) -> impl IntoResponse {
     let filename = resolve_filename(&uri, &ctx.folder);
     if filename.exists() {
         match render(&filename, &ctx) {
             Ok(html) => (StatusCode::OK, Html(html)).into_response(),
-            Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Html("Internal Server Error")).into_response(),
+            Err(err) => {
+                error!("{}", err.chain().map(|cause| cause.to_string()).collect::<Vec<_>>().join(": "));
+                (StatusCode::INTERNAL_SERVER_ERROR, Html(render_error_overlay(&filename, &err))).into_response()
+            }
         }
     } else {
         (
@@ -1,6 +1,14 @@
-use std::{collections::HashMap, ops::Deref};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
-use crate::{CONTEXT, Context, config::AppConfig};
+use crate::{
+    Result,
+    config::AppConfig,
+    content::site::Site,
+    data,
+    filters::{self, Safe},
+    reload::Reloading,
+    url_prefix,
+};
 use once_cell::sync::Lazy;
 use tera::{Tera, Value};
 
@@ -8,40 +16,321 @@ pub static TEMPLATES: Lazy<Tera> = Lazy::new(|| {
     let mut tera = Tera::default();
 
     tera.add_raw_templates(vec![
+        ("__builtins/admonition.html", include_str!("builtins/admonition.html")),
         ("__builtins/error.html", include_str!("builtins/error.html")),
+        ("__builtins/error-overlay.html", include_str!("builtins/error-overlay.html")),
         ("__builtins/toc-item.html", include_str!("builtins/toc-item.html")),
         ("__builtins/toc.html", include_str!("builtins/toc.html")),
         ("__builtins/nav.html", include_str!("builtins/nav.html")),
+        ("__builtins/nav-item.html", include_str!("builtins/nav-item.html")),
         ("__builtins/theme_switch.html", include_str!("builtins/theme_switch.html")),
     ])
     .unwrap();
     tera
 });
 
-pub fn load_templates(config: &AppConfig) -> anyhow::Result<Tera> {
-    let mut tera = Tera::parse(&format!("{}/templates/**/*", config.folder.to_string_lossy()))?;
+/// Default theme, used for every template neither the project nor its theme provide. Its
+/// templates live in `__default/` and only extend each other, so a project shipping just a
+/// `base.html` does not change them. The plain names are aliases extending them.
+pub static DEFAULT_THEME: Lazy<Tera> = Lazy::new(|| {
+    let mut tera = Tera::default();
+
+    let templates = [
+        ("base.html", include_str!("builtins/theme/base.html")),
+        ("page.html", include_str!("builtins/theme/page.html")),
+        ("section.html", include_str!("builtins/theme/section.html")),
+        ("taxonomy.html", include_str!("builtins/theme/taxonomy.html")),
+        ("404.html", include_str!("builtins/theme/404.html")),
+    ];
+    let aliases = templates.map(|(name, _)| (name.to_string(), format!("{{% extends \"__default/{name}\" %}}")));
+    let defaults = templates.map(|(name, source)| (format!("__default/{name}"), source.to_string()));
+    tera.add_raw_templates(defaults.into_iter().chain(aliases)).unwrap();
+    tera
+});
+
+/// Compiled templates of a site. In serve mode they are compiled again when a template file
+/// changed, otherwise only once.
+#[derive(Debug, Default)]
+pub struct Templates(Reloading<Tera>);
+
+impl Templates {
+    pub fn get(&self, config: &AppConfig) -> Result<Arc<Tera>> {
+        self.0.get(&template_dirs(config), || load_templates(config))
+    }
+}
+
+/// Template directories of the project and its theme, earlier directories take precedence.
+fn template_dirs(config: &AppConfig) -> Vec<PathBuf> {
+    let theme_templates = config.theme_dir().map(|dir| dir.join("templates"));
+    [config.folder.join("templates")].into_iter().chain(theme_templates).collect()
+}
+
+pub fn load_templates(config: &AppConfig) -> Result<Tera> {
+    let mut tera = Tera::default();
+    // templates of the project replace theme templates with the same name
+    for dir in template_dirs(config) {
+        tera.extend(&Tera::parse(&format!("{}/**/*", dir.to_string_lossy()))?)?;
+    }
     tera.extend(&TEMPLATES)?;
+    tera.extend(&DEFAULT_THEME)?;
     tera.build_inheritance_chains()?;
 
-    let prefix = if let Some(meta) = &config.project_config.project {
-        meta.root_dir.clone().unwrap_or_default()
-    } else {
-        String::new()
-    };
-
+    let project_config = config.project_config.clone();
     tera.register_filter("url", move |value: &Value, _: &HashMap<String, Value>| -> tera::Result<Value> {
         let path = value.as_str().ok_or("Expected a string for path")?;
+        Ok(Value::String(format!("{}{path}", url_prefix(&project_config))))
+    });
+
+    let prefix = url_prefix(&config.project_config);
+    let markdown_config = config.project_config.markdown.clone();
+    tera.register_filter(
+        "markdown",
+        Safe(move |value: &Value, args: &HashMap<String, Value>| filters::markdown(value, args, &markdown_config)),
+    );
+    tera.register_filter("date_format", filters::date_format);
+    tera.register_filter("slugify", filters::slugify);
+    let markdown_config = config.project_config.markdown.clone();
+    tera.register_filter("reading_time", move |value: &Value, _: &HashMap<String, Value>| {
+        filters::reading_time(value, &markdown_config)
+    });
+    let base_url = config.project_config.project.as_ref().and_then(|meta| meta.base_url.clone());
+    let absolute_prefix = prefix.clone();
+    tera.register_filter("absolute_url", move |value: &Value, _: &HashMap<String, Value>| {
+        filters::absolute_url(value, base_url.as_deref(), &absolute_prefix)
+    });
+    let (asset_dirs, asset_prefix) = (config.asset_dirs(), prefix.clone());
+    tera.register_filter("asset", move |value: &Value, _: &HashMap<String, Value>| {
+        filters::asset(value, &asset_dirs, &asset_prefix)
+    });
+    tera.register_filter("truncate_html", Safe(filters::truncate_html));
+    tera.register_filter("json_ld", Safe(filters::json_ld));
 
-        let ctx_guard = CONTEXT.read().unwrap();
-        let ctx = ctx_guard.deref();
+    let (folder, files) = (config.folder.clone(), data::FileCache::default());
+    tera.register_function("load_data", move |args: &HashMap<String, Value>| -> tera::Result<Value> {
+        let path = args.get("path").and_then(Value::as_str).ok_or("Expected a string for path")?;
+        files
+            .load(&folder.join(path))
+            .map_err(|err| tera::Error::chain("Failed to call `load_data`", err))
+    });
 
-        let full_url = if ctx == &Context::Build {
-            format!("{prefix}{path}")
-        } else {
-            path.to_string()
+    let site = Site::new(config.library.clone(), config.folder.join("content"), prefix);
+    let get_page = site.clone();
+    tera.register_function("get_page", move |args: &HashMap<String, Value>| -> tera::Result<Value> {
+        get_page.page(string_arg(args, "path")?)
+    });
+    let get_section = site.clone();
+    tera.register_function("get_section", move |args: &HashMap<String, Value>| -> tera::Result<Value> {
+        get_section.section(string_arg(args, "path")?)
+    });
+    let get_url = site.clone();
+    tera.register_function("get_url", move |args: &HashMap<String, Value>| -> tera::Result<Value> {
+        get_url.url(string_arg(args, "path")?).map(Value::String)
+    });
+    tera.register_function("get_pages", move |args: &HashMap<String, Value>| -> tera::Result<Value> {
+        let optional = |name| args.get(name).map(|_| string_arg(args, name)).transpose();
+        let limit = match args.get("limit") {
+            Some(limit) => Some(limit.as_u64().ok_or("Expected a number for limit")? as usize),
+            None => None,
         };
-        Ok(Value::String(full_url))
+        let section = optional("section")?.unwrap_or_default();
+        let pages = site.pages(section, optional("sort_by")?, limit, optional("filter")?)?;
+        Ok(Value::Array(pages))
     });
 
     Ok(tera)
 }
+
+fn string_arg<'a>(args: &'a HashMap<String, Value>, name: &str) -> tera::Result<&'a str> {
+    args.get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Expected a string for {name}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{ProjectConfig, resolve_nav},
+        content::page::Page,
+    };
+    use std::{fs, path::Path};
+
+    #[test]
+    fn test_nested_toc() {
+        let page = Page::from_string("# A\n## B\n### C\n#### D\n## E").unwrap();
+        let mut context = tera::Context::new();
+        context.insert("page", &page);
+        let html = TEMPLATES.render("__builtins/toc.html", &context).unwrap();
+        for id in ["a", "b", "c", "d", "e"] {
+            assert!(html.contains(&format!("<a href=\"#{id}\">")));
+        }
+        assert_eq!(html.matches("<ul").count(), 4);
+    }
+
+    #[test]
+    fn test_nested_nav() {
+        let mut config: ProjectConfig =
+            serde_yaml::from_str("nav:\n- title: Docs\n  url: /docs/\n  children:\n  - Install: /docs/install\n- Repo: https://example.com").unwrap();
+        resolve_nav(&mut config.nav, Path::new("content"), &HashMap::new()).unwrap();
+        let mut tera = Tera::default();
+        tera.extend(&TEMPLATES).unwrap();
+        tera.register_filter("url", |value: &Value, _: &HashMap<String, Value>| Ok(value.clone()));
+
+        let mut context = tera::Context::new();
+        context.insert("config", &config);
+        context.insert("path", "/docs/install");
+        let html = tera.render("__builtins/nav.html", &context).unwrap();
+        assert_eq!(html.matches("class=\"active\"").count(), 2);
+        assert_eq!(html.matches("<ul role=\"list\">").count(), 2);
+        assert!(html.contains("href=\"https:&#x2F;&#x2F;example.com\"  class=\"external\""));
+    }
+
+    #[test]
+    fn test_templates_reload_on_change() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path().to_path_buf();
+        fs::create_dir_all(folder.join("templates")).unwrap();
+        fs::write(folder.join("templates").join("page.html"), "first").unwrap();
+        let config = AppConfig {
+            folder: folder.clone(),
+            ..Default::default()
+        };
+
+        let first = config.tera().unwrap();
+        assert!(Arc::ptr_eq(&first, &config.tera().unwrap()));
+
+        fs::write(folder.join("templates").join("page.html"), "second!").unwrap();
+        // changes are only picked up once the check interval passed
+        assert!(Arc::ptr_eq(&first, &config.tera().unwrap()));
+        std::thread::sleep(crate::reload::CHECK_INTERVAL);
+        let second = config.tera().unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(second.render("page.html", &tera::Context::new()).unwrap(), "second!");
+    }
+
+    #[test]
+    fn test_theme_templates() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path().to_path_buf();
+        let theme_templates = folder.join("themes").join("docs").join("templates");
+        fs::create_dir_all(&theme_templates).unwrap();
+        fs::create_dir_all(folder.join("templates")).unwrap();
+        fs::write(theme_templates.join("base.html"), "theme {% block body %}{% endblock %}").unwrap();
+        fs::write(
+            theme_templates.join("page.html"),
+            "{% extends \"base.html\" %}{% block body %}page{% endblock %}",
+        )
+        .unwrap();
+        fs::write(folder.join("templates").join("base.html"), "project {% block body %}{% endblock %}").unwrap();
+
+        let mut config = AppConfig {
+            folder,
+            ..Default::default()
+        };
+        config.project_config.theme = Some("docs".to_string());
+        let tera = load_templates(&config).unwrap();
+        assert_eq!(tera.render("page.html", &tera::Context::new()).unwrap(), "project page");
+    }
+
+    #[test]
+    fn test_default_theme() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path().to_path_buf();
+        let library = [("index.md", "# Home\n\nWelcome"), ("guide.md", "# Guide\n\nRead me")]
+            .into_iter()
+            .map(|(path, markdown)| (folder.join("content").join(path), Page::from_string(markdown).unwrap()))
+            .collect();
+        let config = AppConfig {
+            folder,
+            library: Arc::new(library),
+            ..Default::default()
+        };
+        let tera = load_templates(&config).unwrap();
+
+        let mut context = tera::Context::new();
+        context.insert("config", &config.project_config);
+        context.insert("page", &config.library.values().next().unwrap());
+        context.insert("content", "<p>Welcome</p>");
+        context.insert("path", "/");
+        let html = tera.render("section.html", &context).unwrap();
+        assert!(html.contains("<p>Welcome</p>"));
+        assert!(html.contains("<a href=\"&#x2F;guide\">Guide</a>"));
+        assert!(html.contains("SwitchColorMode.js"));
+    }
+
+    #[test]
+    fn test_default_theme_ignores_project_base() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path().to_path_buf();
+        fs::create_dir_all(folder.join("templates")).unwrap();
+        fs::write(folder.join("templates").join("base.html"), "project {% block content %}{% endblock %}").unwrap();
+        let config = AppConfig {
+            folder,
+            ..Default::default()
+        };
+        let tera = load_templates(&config).unwrap();
+
+        let mut context = tera::Context::new();
+        context.insert("config", &config.project_config);
+        context.insert("page", &Page::from_string("# Guide").unwrap());
+        context.insert("content", "<p>Read me</p>");
+        context.insert("path", "/guide");
+        let html = tera.render("page.html", &context).unwrap();
+        assert!(html.contains("<p>Read me</p>"));
+        assert!(html.starts_with("<!DOCTYPE html>"), "{html}");
+        assert_eq!(tera.render("base.html", &context).unwrap(), "project ");
+    }
+
+    #[test]
+    fn test_load_data() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path().to_path_buf();
+        std::fs::create_dir_all(folder.join("templates")).unwrap();
+        std::fs::write(folder.join("releases.csv"), "version\n1.0\n1.1\n").unwrap();
+        std::fs::write(
+            folder.join("templates").join("releases.html"),
+            "{% for r in load_data(path='releases.csv') %}{{ r.version }} {% endfor %}",
+        )
+        .unwrap();
+
+        let config = AppConfig {
+            folder,
+            ..Default::default()
+        };
+        let tera = load_templates(&config).unwrap();
+        assert_eq!(tera.render("releases.html", &tera::Context::new()).unwrap(), "1.0 1.1 ");
+    }
+
+    #[test]
+    fn test_site_functions() {
+        let temp = tempfile::tempdir().unwrap();
+        let folder = temp.path().to_path_buf();
+        std::fs::create_dir_all(folder.join("templates")).unwrap();
+        std::fs::write(
+            folder.join("templates").join("home.html"),
+            "{% for post in get_pages(section='blog', sort_by='-created_at', limit=1) %}<a href=\"{{ post.url }}\">{{ post.title }}</a>{% endfor %} \
+             {% set blog = get_section(path='blog') %}{% set first = get_page(path='blog/first.md') %}\
+             {{ blog.title }} {{ first.word_count }} {{ get_url(path='blog/index.md') }}",
+        )
+        .unwrap();
+
+        let library = [
+            ("blog/index.md", "---\ntitle: Blog\n---"),
+            ("blog/first.md", "---\ntitle: First\ncreated_at: 2025-01-10\n---\nTwo words"),
+            ("blog/second.md", "---\ntitle: Second\ncreated_at: 2025-03-02\n---"),
+        ]
+        .into_iter()
+        .map(|(path, markdown)| (folder.join("content").join(path), Page::from_string(markdown).unwrap()))
+        .collect();
+        let config = AppConfig {
+            folder,
+            library: Arc::new(library),
+            ..Default::default()
+        };
+        let tera = load_templates(&config).unwrap();
+        assert_eq!(
+            tera.render("home.html", &tera::Context::new()).unwrap(),
+            "<a href=\"&#x2F;blog&#x2F;second\">Second</a> Blog 2 &#x2F;blog&#x2F;"
+        );
+    }
+}